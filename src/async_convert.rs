@@ -0,0 +1,308 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+use crate::converter::{CANCEL_POLL_INTERVAL, StderrLog, ensure_parent_dir};
+use crate::diagnostics;
+use crate::events::{EventSink, ProgressParser};
+use crate::loudness::{LoudnessMeasurement, LoudnessTarget};
+use crate::probe::MediaInfo;
+use crate::staging::StagedOutput;
+use crate::{CancellationToken, ConversionError, ConversionOptions, ProgressEvent, Stage, VideoToAudioConverter};
+
+/// Progress events of a [`VideoToAudioConverter::convert_async`] call.
+///
+/// Ends after the Finished or Failed event. When the conversion future is
+/// dropped, that is `Failed(Cancelled)` once ffmpeg has been stopped.
+#[derive(Debug)]
+pub struct ProgressStream {
+    receiver: UnboundedReceiver<ProgressEvent>,
+}
+
+impl ProgressStream {
+    /// Waits for the next event, like `StreamExt::next`.
+    pub async fn recv(&mut self) -> Option<ProgressEvent> {
+        self.receiver.recv().await
+    }
+}
+
+impl Stream for ProgressStream {
+    type Item = ProgressEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<ProgressEvent>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+// The sink side never blocks, so emitting from async code is fine
+fn progress_channel() -> (EventSink, ProgressStream) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let events = EventSink::new(move |event: &ProgressEvent| {
+        let _ = sender.send(event.clone());
+    });
+    (events, ProgressStream { receiver })
+}
+
+impl VideoToAudioConverter {
+    /// Async version of [`convert`](Self::convert) for tokio runtimes.
+    ///
+    /// Returns the conversion future together with the stream of its
+    /// progress events. Nothing runs until the future is polled; ffprobe and
+    /// ffmpeg then run as tokio processes started through the backend's
+    /// [`spawn_async`](crate::backend::MediaBackend::spawn_async), and their
+    /// output is read without blocking. Dropping the future kills ffmpeg and
+    /// removes the partially written output; a [`CancellationToken`] in
+    /// `options` works too.
+    pub fn convert_async(
+        &self,
+        input_path: &str,
+        output_path: &str,
+        options: &ConversionOptions,
+    ) -> (impl Future<Output = Result<(), ConversionError>> + Send + 'static, ProgressStream) {
+        let (events, stream) = progress_channel();
+        let converter = self.clone();
+        let input_path = input_path.to_string();
+        let output_path = output_path.to_string();
+        let options = options.clone();
+
+        let conversion = async move {
+            let dropped = CancelledOnDrop(Some(events.clone()));
+            let result = converter.do_convert_async(&input_path, &output_path, &options, &events).await;
+            dropped.disarm();
+            events.outcome(result)
+        };
+        (conversion, stream)
+    }
+
+    // `do_convert` on the backend's async path
+    async fn do_convert_async(
+        &self,
+        input_path: &str,
+        output_path: &str,
+        options: &ConversionOptions,
+        events: &EventSink,
+    ) -> Result<(), ConversionError> {
+        let media_info = self.backend.probe_async(input_path).await?;
+        events.emit(ProgressEvent::Started { input_path: input_path.to_string(), media_info: media_info.clone() });
+
+        ensure_parent_dir(output_path)?;
+        let staged = StagedOutput::new(output_path);
+        let duration = match options.loudness_target() {
+            Some(target) => self.convert_normalized_async(input_path, staged.path(), options, &media_info, target, events).await?,
+            None => {
+                let (command, duration) = self.conversion_command(input_path, staged.path(), options, &media_info, &[])?;
+                events.emit(ProgressEvent::StageChanged(Stage::Encoding));
+                self.run_ffmpeg_async(&command, &[staged.path()], options, ProgressParser::new(duration), events).await?;
+                duration
+            }
+        };
+        staged.commit_async(&*self.backend).await?;
+        events.finished(vec![output_path.to_string()], duration);
+        Ok(())
+    }
+
+    // `convert_normalized` on the backend's async path
+    async fn convert_normalized_async(
+        &self,
+        input_path: &str,
+        output_path: &str,
+        options: &ConversionOptions,
+        media_info: &MediaInfo,
+        target: &LoudnessTarget,
+        events: &EventSink,
+    ) -> Result<Option<f64>, ConversionError> {
+        let format = options.resolve_format(output_path)?;
+        options.validate(format)?;
+
+        let (analysis, duration) = self.analysis_command(input_path, options, media_info, target)?;
+        let total = duration.map(|d| d * 2.0);
+        events.emit(ProgressEvent::StageChanged(Stage::Analyzing));
+        let stderr = self.run_ffmpeg_async(&analysis, &[], options, ProgressParser::new(duration).within(0.0, total), events).await?;
+        let measured = LoudnessMeasurement::from_stderr(&stderr.join("\n"))?;
+
+        let (command, duration) = self.normalized_command(input_path, output_path, options, media_info, target, &measured)?;
+        events.emit(ProgressEvent::StageChanged(Stage::Encoding));
+        let parser = ProgressParser::new(duration).within(duration.unwrap_or(0.0), total);
+        self.run_ffmpeg_async(&command, &[output_path], options, parser, events).await?;
+        Ok(duration)
+    }
+
+    // `run_ffmpeg` on the backend's async path: stderr is parsed as ffmpeg
+    // writes it, while the token is polled alongside
+    async fn run_ffmpeg_async(
+        &self,
+        args: &[String],
+        outputs: &[&str],
+        options: &ConversionOptions,
+        parser: ProgressParser,
+        events: &EventSink,
+    ) -> Result<Vec<String>, ConversionError> {
+        let cancel = options.cancellation_token();
+        if cancel.is_some_and(|token| token.is_cancelled()) {
+            return Err(ConversionError::Cancelled);
+        }
+
+        let mut process = self.backend.spawn_async(args, outputs)?;
+        let stderr = process
+            .take_stderr()
+            .ok_or_else(|| ConversionError::IOError("ffmpeg's stderr is not piped".to_string()))?;
+        let mut lines = BufReader::new(stderr).lines();
+        let mut log = StderrLog::new(parser, events.clone());
+
+        let run = async {
+            while let Ok(Some(line)) = lines.next_line().await {
+                log.feed(line);
+            }
+            process.wait().await
+        };
+        let status = tokio::select! {
+            status = run => Some(status),
+            () = cancelled(cancel) => None,
+        };
+        let Some(status) = status else {
+            let _ = process.kill().await;
+            return Err(ConversionError::Cancelled);
+        };
+
+        let status = status.map_err(|e| ConversionError::IOError(e.to_string()))?;
+        // As with `wait_for`, a run failing after cancellation counts as cancelled
+        if !status.success() && cancel.is_some_and(|token| token.is_cancelled()) {
+            return Err(ConversionError::Cancelled);
+        }
+        let log = log.into_lines();
+        if !status.success() {
+            return Err(diagnostics::classify(status, &log));
+        }
+        Ok(log)
+    }
+}
+
+// Resolves once `token` is cancelled, or never without one
+async fn cancelled(token: Option<&CancellationToken>) {
+    match token {
+        Some(token) => {
+            while !token.is_cancelled() {
+                tokio::time::sleep(CANCEL_POLL_INTERVAL).await;
+            }
+        }
+        None => std::future::pending().await,
+    }
+}
+
+// Ends the event stream with `Failed(Cancelled)` when the conversion future
+// is dropped before finishing. It is dropped after the conversion's own
+// state, so ffmpeg has been killed and the staged output removed by then.
+struct CancelledOnDrop(Option<EventSink>);
+
+impl CancelledOnDrop {
+    fn disarm(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for CancelledOnDrop {
+    fn drop(&mut self) {
+        if let Some(events) = self.0.take()
+            && !std::thread::panicking()
+        {
+            events.emit(ProgressEvent::Failed(ConversionError::Cancelled));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{ScriptedBackend, ScriptedRun};
+    use crate::Capabilities;
+
+    #[tokio::test]
+    async fn test_progress_stream() {
+        let (events, mut stream) = progress_channel();
+        events.emit(ProgressEvent::StageChanged(Stage::Encoding));
+        let _ = events.outcome::<()>(Err(ConversionError::Cancelled));
+        drop(events);
+
+        let first = std::future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await;
+        assert!(matches!(first, Some(ProgressEvent::StageChanged(Stage::Encoding))));
+        assert!(matches!(stream.recv().await, Some(ProgressEvent::Failed(ConversionError::Cancelled))));
+        assert!(stream.recv().await.is_none());
+    }
+
+    // A scripted converter that can write MP3 from an input with one audio stream
+    fn scripted(backend: &ScriptedBackend) -> VideoToAudioConverter {
+        let json = r#"{"streams": [{"index": 0, "codec_type": "audio", "codec_name": "aac"}], "format": {"duration": "10.0"}}"#;
+        let backend = backend.clone().media("talk.mp4", MediaInfo::from_json(json).unwrap());
+        let capabilities = Capabilities::parse(" ---\n A....D libmp3lame  MP3\n", " ---\n  E  mp3  MP3\n");
+        VideoToAudioConverter::with_backend(backend, capabilities)
+    }
+
+    fn output_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("vac_async_{}_{}.mp3", name, std::process::id()));
+        path.to_string_lossy().into_owned()
+    }
+
+    #[tokio::test]
+    async fn test_missing_input() {
+        let converter = scripted(&ScriptedBackend::new());
+        let (conversion, mut stream) = converter.convert_async("nonexistent.mp4", "output.mp3", &ConversionOptions::new());
+        assert!(matches!(conversion.await, Err(ConversionError::FileNotFound)));
+        assert!(matches!(stream.recv().await, Some(ProgressEvent::Failed(ConversionError::FileNotFound))));
+        assert!(stream.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_scripted_conversion() {
+        let backend = ScriptedBackend::new().run(ScriptedRun::new("out_time_us=10000000\nprogress=end\n").output("audio"));
+        let converter = scripted(&backend);
+        let output = output_path("convert");
+        let (conversion, mut stream) = converter.convert_async("talk.mp4", &output, &ConversionOptions::new());
+        conversion.await.unwrap();
+
+        let mut events = Vec::new();
+        while let Some(event) = stream.recv().await {
+            events.push(event);
+        }
+        assert!(matches!(events.first(), Some(ProgressEvent::Started { .. })));
+        assert!(events.iter().any(|event| matches!(event, ProgressEvent::Progress(p) if p.percentage == Some(100.0))));
+        assert!(matches!(events.last(), Some(ProgressEvent::Finished(_))));
+        assert_eq!(std::fs::read(&output).unwrap(), b"audio");
+        let _ = std::fs::remove_file(output);
+    }
+
+    #[tokio::test]
+    async fn test_dropping_the_future_cancels() {
+        let backend = ScriptedBackend::new().run(ScriptedRun::new("out_time_us=1000000\nprogress=continue\n").output("partial").until_killed());
+        let converter = scripted(&backend);
+        let (conversion, mut stream) = converter.convert_async("talk.mp4", &output_path("drop"), &ConversionOptions::new());
+        let task = tokio::spawn(conversion);
+        while !matches!(stream.recv().await, Some(ProgressEvent::Progress(_))) {}
+        task.abort();
+
+        let mut last = None;
+        while let Some(event) = stream.recv().await {
+            last = Some(event);
+        }
+        assert!(matches!(last, Some(ProgressEvent::Failed(ConversionError::Cancelled))), "{:?}", last);
+        assert!(!std::path::Path::new(backend.commands()[0].last().unwrap()).exists());
+    }
+
+    #[tokio::test]
+    async fn test_cancellation_token() {
+        let backend = ScriptedBackend::new().run(ScriptedRun::new("out_time_us=1000000\nprogress=continue\n").output("partial").until_killed());
+        let converter = scripted(&backend);
+        let token = CancellationToken::new();
+        let options = ConversionOptions::new().cancellation(token.clone());
+        let (conversion, mut stream) = converter.convert_async("talk.mp4", &output_path("token"), &options);
+        let task = tokio::spawn(conversion);
+        while !matches!(stream.recv().await, Some(ProgressEvent::Progress(_))) {}
+        token.cancel();
+
+        assert!(matches!(task.await.unwrap(), Err(ConversionError::Cancelled)));
+        assert!(!std::path::Path::new(backend.commands()[0].last().unwrap()).exists());
+    }
+}
@@ -0,0 +1,463 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+#[cfg(feature = "async")]
+use std::future::Future;
+use std::io::{self, Cursor, Read, Write};
+use std::path::Path;
+#[cfg(feature = "async")]
+use std::pin::Pin;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "async")]
+use tokio::io::AsyncRead;
+
+use crate::ConversionError;
+use crate::probe::{self, MediaInfo};
+
+/// How an ffmpeg process ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessExit {
+    /// `None` when the process was terminated by a signal.
+    pub code: Option<i32>,
+}
+
+impl ProcessExit {
+    /// Whether the process exited with status 0.
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+impl From<ExitStatus> for ProcessExit {
+    fn from(status: ExitStatus) -> Self {
+        Self { code: status.code() }
+    }
+}
+
+/// Which of ffmpeg's standard streams the converter connects to. stderr is
+/// always piped; the others are null unless asked for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Piping {
+    /// Pipe stdin, for conversions from a reader.
+    pub stdin: bool,
+    /// Pipe stdout, for conversions to a writer.
+    pub stdout: bool,
+}
+
+/// A boxed future, as returned by the async methods of [`MediaBackend`] and
+/// [`AsyncMediaProcess`].
+#[cfg(feature = "async")]
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Runs ffprobe and ffmpeg for a
+/// [`VideoToAudioConverter`](crate::VideoToAudioConverter).
+///
+/// The converter builds ffmpeg's arguments, feeds and drains its pipes and
+/// parses the log and `-progress pipe:2` blocks it writes to stderr; the
+/// backend only runs it. [`FfmpegBackend`] starts real processes and
+/// [`ScriptedBackend`] replays recorded runs for tests. Both also implement
+/// the async methods behind the `async` feature.
+pub trait MediaBackend: Send + Sync + fmt::Debug {
+    /// Reads the container, streams and chapters of `input_path`, failing with
+    /// `FileNotFound` if there is no such input.
+    fn probe(&self, input_path: &str) -> Result<MediaInfo, ConversionError>;
+
+    /// Starts ffmpeg with `args`. `outputs` are the files the run is expected
+    /// to write; they are also in `args`, which is all real ffmpeg needs.
+    fn spawn(&self, args: &[String], piping: Piping, outputs: &[&str]) -> Result<Box<dyn MediaProcess>, ConversionError>;
+
+    /// [`probe`](Self::probe) for async callers. By default it calls `probe`
+    /// in place, which only suits backends that answer without blocking.
+    #[cfg(feature = "async")]
+    fn probe_async<'a>(&'a self, input_path: &'a str) -> BoxFuture<'a, Result<MediaInfo, ConversionError>> {
+        Box::pin(async move { self.probe(input_path) })
+    }
+
+    /// [`spawn`](Self::spawn) for async callers, with stdin and stdout null.
+    /// By default it fails: a backend has to opt in to running ffmpeg without
+    /// blocking.
+    #[cfg(feature = "async")]
+    fn spawn_async(&self, args: &[String], outputs: &[&str]) -> Result<Box<dyn AsyncMediaProcess>, ConversionError> {
+        let _ = outputs;
+        Err(ConversionError::FFmpegError(format!("{:?} cannot run ffmpeg {} asynchronously", self, args.join(" "))))
+    }
+
+    /// The ffmpeg executable, for backends that run one.
+    fn ffmpeg_path(&self) -> Option<&str> {
+        None
+    }
+
+    /// The ffprobe executable, for backends that run one.
+    fn ffprobe_path(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// An ffmpeg run started by a [`MediaBackend`].
+pub trait MediaProcess: Send {
+    /// ffmpeg's stdin, if it was piped. Each stream can be taken once.
+    fn take_stdin(&mut self) -> Option<Box<dyn Write + Send>>;
+    /// ffmpeg's stdout, if it was piped.
+    fn take_stdout(&mut self) -> Option<Box<dyn Read + Send>>;
+    /// ffmpeg's stderr, which is always piped.
+    fn take_stderr(&mut self) -> Option<Box<dyn Read + Send>>;
+
+    /// How the run ended, or `None` while it is still going.
+    fn try_wait(&mut self) -> io::Result<Option<ProcessExit>>;
+    /// Waits for the run to end.
+    fn wait(&mut self) -> io::Result<ProcessExit>;
+    /// Stops the run; it still has to be waited for.
+    fn kill(&mut self) -> io::Result<()>;
+}
+
+/// An ffmpeg run started by [`MediaBackend::spawn_async`]. Dropping it stops
+/// the run.
+#[cfg(feature = "async")]
+pub trait AsyncMediaProcess: Send {
+    /// ffmpeg's stderr, which is always piped. It can be taken once.
+    fn take_stderr(&mut self) -> Option<Box<dyn AsyncRead + Send + Unpin>>;
+    /// Waits for the run to end.
+    fn wait(&mut self) -> BoxFuture<'_, io::Result<ProcessExit>>;
+    /// Stops the run and waits for it to end.
+    fn kill(&mut self) -> BoxFuture<'_, io::Result<()>>;
+}
+
+/// Runs the ffmpeg and ffprobe executables at the given paths.
+#[derive(Debug, Clone)]
+pub struct FfmpegBackend {
+    ffmpeg_path: String,
+    ffprobe_path: String,
+}
+
+impl FfmpegBackend {
+    /// A backend running the executables at these paths, which are not
+    /// checked; [`FfmpegLocator`](crate::FfmpegLocator) finds working ones.
+    pub fn new(ffmpeg_path: impl Into<String>, ffprobe_path: impl Into<String>) -> Self {
+        Self {
+            ffmpeg_path: ffmpeg_path.into(),
+            ffprobe_path: ffprobe_path.into(),
+        }
+    }
+}
+
+impl MediaBackend for FfmpegBackend {
+    fn probe(&self, input_path: &str) -> Result<MediaInfo, ConversionError> {
+        if !Path::new(input_path).exists() {
+            return Err(ConversionError::FileNotFound);
+        }
+        probe::probe(&self.ffprobe_path, input_path)
+    }
+
+    fn spawn(&self, args: &[String], piping: Piping, _outputs: &[&str]) -> Result<Box<dyn MediaProcess>, ConversionError> {
+        let stdio = |piped: bool| if piped { Stdio::piped() } else { Stdio::null() };
+        let child = Command::new(&self.ffmpeg_path)
+            .args(args)
+            .stdin(stdio(piping.stdin))
+            .stdout(stdio(piping.stdout))
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| ConversionError::IOError(e.to_string()))?;
+        Ok(Box::new(child))
+    }
+
+    #[cfg(feature = "async")]
+    fn probe_async<'a>(&'a self, input_path: &'a str) -> BoxFuture<'a, Result<MediaInfo, ConversionError>> {
+        Box::pin(async move {
+            if !Path::new(input_path).exists() {
+                return Err(ConversionError::FileNotFound);
+            }
+            let output = tokio::process::Command::new(&self.ffprobe_path)
+                .args(probe::probe_args(input_path))
+                .stdin(Stdio::null())
+                .kill_on_drop(true)
+                .output()
+                .await
+                .map_err(|e| ConversionError::IOError(e.to_string()))?;
+            probe::from_output(output)
+        })
+    }
+
+    #[cfg(feature = "async")]
+    fn spawn_async(&self, args: &[String], _outputs: &[&str]) -> Result<Box<dyn AsyncMediaProcess>, ConversionError> {
+        let child = tokio::process::Command::new(&self.ffmpeg_path)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            // Dropping the conversion future stops ffmpeg
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| ConversionError::IOError(e.to_string()))?;
+        Ok(Box::new(child))
+    }
+
+    fn ffmpeg_path(&self) -> Option<&str> {
+        Some(&self.ffmpeg_path)
+    }
+
+    fn ffprobe_path(&self) -> Option<&str> {
+        Some(&self.ffprobe_path)
+    }
+}
+
+impl MediaProcess for Child {
+    fn take_stdin(&mut self) -> Option<Box<dyn Write + Send>> {
+        self.stdin.take().map(|stdin| Box::new(stdin) as Box<dyn Write + Send>)
+    }
+
+    fn take_stdout(&mut self) -> Option<Box<dyn Read + Send>> {
+        self.stdout.take().map(|stdout| Box::new(stdout) as Box<dyn Read + Send>)
+    }
+
+    fn take_stderr(&mut self) -> Option<Box<dyn Read + Send>> {
+        self.stderr.take().map(|stderr| Box::new(stderr) as Box<dyn Read + Send>)
+    }
+
+    fn try_wait(&mut self) -> io::Result<Option<ProcessExit>> {
+        Child::try_wait(self).map(|status| status.map(ProcessExit::from))
+    }
+
+    fn wait(&mut self) -> io::Result<ProcessExit> {
+        Child::wait(self).map(ProcessExit::from)
+    }
+
+    fn kill(&mut self) -> io::Result<()> {
+        Child::kill(self)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncMediaProcess for tokio::process::Child {
+    fn take_stderr(&mut self) -> Option<Box<dyn AsyncRead + Send + Unpin>> {
+        self.stderr.take().map(|stderr| Box::new(stderr) as Box<dyn AsyncRead + Send + Unpin>)
+    }
+
+    fn wait(&mut self) -> BoxFuture<'_, io::Result<ProcessExit>> {
+        Box::pin(async move { tokio::process::Child::wait(self).await.map(ProcessExit::from) })
+    }
+
+    fn kill(&mut self) -> BoxFuture<'_, io::Result<()>> {
+        Box::pin(tokio::process::Child::kill(self))
+    }
+}
+
+/// One recorded ffmpeg run for a [`ScriptedBackend`]: what it printed and
+/// how it ended.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptedRun {
+    stderr: String,
+    stdout: Vec<u8>,
+    output: Option<Vec<u8>>,
+    output_media: Option<MediaInfo>,
+    exit_code: i32,
+    until_killed: bool,
+}
+
+impl ScriptedRun {
+    /// A run that prints `stderr`, e.g. a transcript captured with
+    /// `-loglevel level+info -progress pipe:2`, and exits with status 0.
+    pub fn new(stderr: &str) -> Self {
+        Self {
+            stderr: stderr.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Exits with `code` instead of 0, so the converter classifies the run
+    /// as failed from its stderr.
+    pub fn exit_code(mut self, code: i32) -> Self {
+        self.exit_code = code;
+        self
+    }
+
+    /// Writes `bytes` to stdout, for conversions to a writer.
+    pub fn stdout(mut self, bytes: impl Into<Vec<u8>>) -> Self {
+        self.stdout = bytes.into();
+        self
+    }
+
+    /// Writes `bytes` to each output file of the run. Runs that end without
+    /// writing their outputs fail the converter's check of them.
+    pub fn output(mut self, bytes: impl Into<Vec<u8>>) -> Self {
+        self.output = Some(bytes.into());
+        self
+    }
+
+    /// What probes of the written outputs report. By default they hold one
+    /// audio stream, which is all the converter checks for.
+    pub fn output_media(mut self, media_info: MediaInfo) -> Self {
+        self.output_media = Some(media_info);
+        self
+    }
+
+    /// Keeps running after printing its stderr until it is killed, so a
+    /// test can cancel it.
+    pub fn until_killed(mut self) -> Self {
+        self.until_killed = true;
+        self
+    }
+
+    // How the run ends on its own; None if it waits to be killed
+    fn exit(&self) -> Option<ProcessExit> {
+        match self.until_killed {
+            true => None,
+            false => Some(ProcessExit { code: Some(self.exit_code) }),
+        }
+    }
+}
+
+/// A [`MediaBackend`] that replays [`ScriptedRun`]s instead of running
+/// ffmpeg, so the conversion pipeline can be tested deterministically.
+///
+/// Each spawn takes the next run in the order they were added and records
+/// its arguments. Probes answer with the media info registered for the
+/// input path, or for an output a run wrote, and fail with `FileNotFound`
+/// for any other. Clones share the same script.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptedBackend {
+    media: Arc<Mutex<HashMap<String, MediaInfo>>>,
+    runs: Arc<Mutex<VecDeque<ScriptedRun>>>,
+    commands: Arc<Mutex<Vec<Vec<String>>>>,
+}
+
+impl ScriptedBackend {
+    /// A backend with no media and no runs scripted yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answers probes of `input_path` with `media_info`, e.g. parsed from
+    /// recorded ffprobe output with [`MediaInfo::from_json`].
+    pub fn media(self, input_path: &str, media_info: MediaInfo) -> Self {
+        self.media.lock().unwrap().insert(input_path.to_string(), media_info);
+        self
+    }
+
+    /// Queues `run` for the next ffmpeg run not scripted yet.
+    pub fn run(self, run: ScriptedRun) -> Self {
+        self.runs.lock().unwrap().push_back(run);
+        self
+    }
+
+    /// The arguments of every ffmpeg run started so far.
+    pub fn commands(&self) -> Vec<Vec<String>> {
+        self.commands.lock().unwrap().clone()
+    }
+
+    /// Runs that were scripted but never started.
+    pub fn remaining_runs(&self) -> usize {
+        self.runs.lock().unwrap().len()
+    }
+
+    // Records `args`, takes the next run and writes its outputs
+    fn start(&self, args: &[String], outputs: &[&str]) -> Result<ScriptedRun, ConversionError> {
+        self.commands.lock().unwrap().push(args.to_vec());
+        let run = self.runs.lock().unwrap().pop_front().ok_or_else(|| {
+            ConversionError::FFmpegError(format!("no scripted run left for ffmpeg {}", args.join(" ")))
+        })?;
+
+        if let Some(bytes) = &run.output {
+            let media_info = run.output_media.clone().unwrap_or_else(audio_only);
+            for output in outputs {
+                std::fs::write(output, bytes).map_err(|e| ConversionError::IOError(e.to_string()))?;
+                self.media.lock().unwrap().insert(output.to_string(), media_info.clone());
+            }
+        }
+        Ok(run)
+    }
+}
+
+impl MediaBackend for ScriptedBackend {
+    fn probe(&self, input_path: &str) -> Result<MediaInfo, ConversionError> {
+        self.media.lock().unwrap().get(input_path).cloned().ok_or(ConversionError::FileNotFound)
+    }
+
+    fn spawn(&self, args: &[String], piping: Piping, outputs: &[&str]) -> Result<Box<dyn MediaProcess>, ConversionError> {
+        let run = self.start(args, outputs)?;
+        Ok(Box::new(ScriptedProcess {
+            stdin: piping.stdin.then(|| Box::new(io::sink()) as Box<dyn Write + Send>),
+            stdout: piping.stdout.then(|| Box::new(Cursor::new(run.stdout.clone())) as Box<dyn Read + Send>),
+            stderr: Some(Box::new(Cursor::new(run.stderr.clone().into_bytes()))),
+            exit: run.exit(),
+        }))
+    }
+
+    #[cfg(feature = "async")]
+    fn spawn_async(&self, args: &[String], outputs: &[&str]) -> Result<Box<dyn AsyncMediaProcess>, ConversionError> {
+        let run = self.start(args, outputs)?;
+        Ok(Box::new(ScriptedAsyncProcess {
+            stderr: Some(Cursor::new(run.stderr.clone().into_bytes())),
+            exit: run.exit(),
+        }))
+    }
+}
+
+// A single audio stream, as a scripted run's outputs are probed by default
+fn audio_only() -> MediaInfo {
+    MediaInfo::from_json(r#"{"streams": [{"index": 0, "codec_type": "audio"}]}"#).unwrap()
+}
+
+struct ScriptedProcess {
+    stdin: Option<Box<dyn Write + Send>>,
+    stdout: Option<Box<dyn Read + Send>>,
+    stderr: Option<Box<dyn Read + Send>>,
+    // None while a run scripted to wait for its kill is still going
+    exit: Option<ProcessExit>,
+}
+
+impl MediaProcess for ScriptedProcess {
+    fn take_stdin(&mut self) -> Option<Box<dyn Write + Send>> {
+        self.stdin.take()
+    }
+
+    fn take_stdout(&mut self) -> Option<Box<dyn Read + Send>> {
+        self.stdout.take()
+    }
+
+    fn take_stderr(&mut self) -> Option<Box<dyn Read + Send>> {
+        self.stderr.take()
+    }
+
+    fn try_wait(&mut self) -> io::Result<Option<ProcessExit>> {
+        Ok(self.exit)
+    }
+
+    fn wait(&mut self) -> io::Result<ProcessExit> {
+        self.exit.ok_or_else(|| io::Error::other("scripted run only ends when killed"))
+    }
+
+    fn kill(&mut self) -> io::Result<()> {
+        self.exit.get_or_insert(ProcessExit { code: None });
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+struct ScriptedAsyncProcess {
+    stderr: Option<Cursor<Vec<u8>>>,
+    exit: Option<ProcessExit>,
+}
+
+#[cfg(feature = "async")]
+impl AsyncMediaProcess for ScriptedAsyncProcess {
+    fn take_stderr(&mut self) -> Option<Box<dyn AsyncRead + Send + Unpin>> {
+        self.stderr.take().map(|stderr| Box::new(stderr) as Box<dyn AsyncRead + Send + Unpin>)
+    }
+
+    fn wait(&mut self) -> BoxFuture<'_, io::Result<ProcessExit>> {
+        let exit = self.exit;
+        Box::pin(async move {
+            match exit {
+                Some(exit) => Ok(exit),
+                // Only a kill, i.e. cancelling or dropping the conversion, ends it
+                None => std::future::pending().await,
+            }
+        })
+    }
+
+    fn kill(&mut self) -> BoxFuture<'_, io::Result<()>> {
+        self.exit.get_or_insert(ProcessExit { code: None });
+        Box::pin(std::future::ready(Ok(())))
+    }
+}
@@ -0,0 +1,395 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::ledger::{Fingerprint, Ledger, LedgerStatus};
+use crate::options::ConversionOptions;
+use crate::{ConversionError, VideoToAudioConverter};
+
+pub use crate::ledger::LEDGER_FILE;
+
+// Input extensions picked up when scanning a directory
+const MEDIA_EXTENSIONS: &[&str] = &[
+    "mp4", "m4v", "mkv", "mov", "avi", "wmv", "flv", "webm", "mpg", "mpeg", "ts", "mts", "m2ts", "3gp", "asf",
+    "ogv", "vob",
+];
+
+/// How [`collect_jobs`] finds inputs and [`run_batch`] runs them.
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    recursive: bool,
+    jobs: usize,
+    overwrite: bool,
+    ledger: Option<PathBuf>,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            recursive: false,
+            jobs: thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            overwrite: false,
+            ledger: None,
+        }
+    }
+}
+
+impl BatchOptions {
+    /// Non-recursive, one job per CPU, existing outputs kept, no ledger.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Descend into subdirectories of directory sources.
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// Number of conversions run at the same time (default: CPU count).
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        self
+    }
+
+    /// Convert again even when the output already exists.
+    pub fn overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// Job ledger that lets a rerun skip inputs converted by an earlier run,
+    /// retry failed ones and reconvert inputs modified since.
+    pub fn ledger(mut self, path: Option<PathBuf>) -> Self {
+        self.ledger = path;
+        self
+    }
+
+    /// The ledger set with [`ledger`](Self::ledger), if any.
+    pub fn ledger_path(&self) -> Option<&Path> {
+        self.ledger.as_deref()
+    }
+}
+
+/// One input and the output it converts to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchJob {
+    /// The input file.
+    pub input: PathBuf,
+    /// Where its audio is written.
+    pub output: PathBuf,
+}
+
+/// What happened to one job of a batch.
+#[derive(Debug)]
+pub enum JobOutcome {
+    /// The output was written.
+    Converted,
+    /// Nothing was done, for the reason given.
+    Skipped(String),
+    /// The conversion failed.
+    Failed(ConversionError),
+}
+
+/// A finished job of a batch.
+#[derive(Debug)]
+pub struct BatchResult {
+    /// The job that ran.
+    pub job: BatchJob,
+    /// How it ended.
+    pub outcome: JobOutcome,
+    /// How long it took.
+    pub elapsed: Duration,
+}
+
+/// Every result of a batch, in the order the jobs finished.
+#[derive(Debug, Default)]
+pub struct BatchSummary {
+    /// One result per job.
+    pub results: Vec<BatchResult>,
+    /// How long the whole batch took.
+    pub elapsed: Duration,
+}
+
+impl BatchSummary {
+    /// Number of jobs that were converted.
+    pub fn converted(&self) -> usize {
+        self.results.iter().filter(|r| matches!(r.outcome, JobOutcome::Converted)).count()
+    }
+
+    /// Number of jobs that were skipped.
+    pub fn skipped(&self) -> usize {
+        self.results.iter().filter(|r| matches!(r.outcome, JobOutcome::Skipped(_))).count()
+    }
+
+    /// Number of jobs that failed.
+    pub fn failed(&self) -> usize {
+        self.results.iter().filter(|r| matches!(r.outcome, JobOutcome::Failed(_))).count()
+    }
+}
+
+/// Expands files, directories and glob patterns into jobs whose outputs
+/// mirror each input's location below its source into `output_root`, with
+/// the extension replaced by `extension`.
+pub fn collect_jobs(
+    sources: &[String],
+    output_root: &Path,
+    extension: &str,
+    options: &BatchOptions,
+) -> Result<Vec<BatchJob>, ConversionError> {
+    let mut seen = HashSet::new();
+    let mut jobs = Vec::new();
+
+    for source in sources {
+        let path = Path::new(source);
+        let (base, files) = if is_glob(source) {
+            let matches = glob::glob(source)
+                .map_err(|e| ConversionError::InvalidOptions(format!("invalid pattern '{}': {}", source, e)))?
+                .filter_map(Result::ok)
+                .filter(|p| p.is_file())
+                .collect();
+            (glob_base(source), matches)
+        } else if path.is_dir() {
+            let mut files = Vec::new();
+            scan_dir(path, options.recursive, &mut files)?;
+            (path.to_path_buf(), files)
+        } else if path.is_file() {
+            (path.parent().map(Path::to_path_buf).unwrap_or_default(), vec![path.to_path_buf()])
+        } else {
+            return Err(ConversionError::FileNotFound);
+        };
+
+        let mut files = files;
+        files.sort();
+        for input in files {
+            if !seen.insert(input.clone()) {
+                continue;
+            }
+            let relative = input.strip_prefix(&base).unwrap_or(&input);
+            let relative: PathBuf = relative.components().filter(|c| matches!(c, Component::Normal(_))).collect();
+            let output = output_root.join(relative).with_extension(extension);
+            jobs.push(BatchJob { input, output });
+        }
+    }
+
+    Ok(jobs)
+}
+
+/// Runs the jobs on a pool of `options.jobs` worker threads and calls
+/// `on_result` from the worker as each one finishes. With a ledger, every
+/// finished job is recorded right away so an interrupted batch can resume.
+pub fn run_batch<R>(
+    converter: &VideoToAudioConverter,
+    jobs: Vec<BatchJob>,
+    conversion: &ConversionOptions,
+    options: &BatchOptions,
+    on_result: R,
+) -> Result<BatchSummary, ConversionError>
+where
+    R: Fn(&BatchResult) + Sync,
+{
+    let start = Instant::now();
+    let ledger = options.ledger.as_deref().map(Ledger::open).transpose()?.map(Mutex::new);
+    let mut claimed: HashMap<PathBuf, PathBuf> = HashMap::new();
+    let mut results = Vec::new();
+    let mut queue = Vec::new();
+
+    for job in jobs {
+        // Two inputs like a.mp4 and a.mkv would otherwise write the same file
+        if let Some(first) = claimed.get(&job.output) {
+            let reason = format!("output also produced by {}", first.display());
+            results.push(finish(job, JobOutcome::Skipped(reason), Duration::ZERO, &on_result));
+        } else {
+            claimed.insert(job.output.clone(), job.input.clone());
+            queue.push(job);
+        }
+    }
+    queue.reverse();
+
+    let queue = Mutex::new(queue);
+    let results = Mutex::new(results);
+    let cancel = conversion.cancellation_token();
+
+    thread::scope(|scope| {
+        for _ in 0..options.jobs {
+            scope.spawn(|| {
+                while let Some(job) = queue.lock().unwrap().pop() {
+                    let job_start = Instant::now();
+                    let outcome = if cancel.is_some_and(|token| token.is_cancelled()) {
+                        JobOutcome::Skipped("batch cancelled".to_string())
+                    } else {
+                        process_job(converter, &job, conversion, options, ledger.as_ref())
+                    };
+                    let result = finish(job, outcome, job_start.elapsed(), &on_result);
+                    results.lock().unwrap().push(result);
+                }
+            });
+        }
+    });
+
+    Ok(BatchSummary {
+        results: results.into_inner().unwrap(),
+        elapsed: start.elapsed(),
+    })
+}
+
+fn process_job(
+    converter: &VideoToAudioConverter,
+    job: &BatchJob,
+    conversion: &ConversionOptions,
+    options: &BatchOptions,
+    ledger: Option<&Mutex<Ledger>>,
+) -> JobOutcome {
+    let Some(ledger) = ledger else {
+        if !options.overwrite && job.output.exists() {
+            return JobOutcome::Skipped("output already exists".to_string());
+        }
+        return convert_job(converter, job, conversion);
+    };
+
+    // Failed and changed inputs are converted again even if an output exists
+    match ledger.lock().unwrap().status(job) {
+        LedgerStatus::Completed if !options.overwrite => {
+            return JobOutcome::Skipped("already converted".to_string());
+        }
+        LedgerStatus::New if !options.overwrite && job.output.exists() => {
+            return JobOutcome::Skipped("output already exists".to_string());
+        }
+        _ => {}
+    }
+
+    let fingerprint = match Fingerprint::of(&job.input) {
+        Ok(fingerprint) => fingerprint,
+        Err(e) => return JobOutcome::Failed(e),
+    };
+    let outcome = convert_job(converter, job, conversion);
+    let result = match &outcome {
+        JobOutcome::Converted => Ok(()),
+        JobOutcome::Failed(e) => Err(e),
+        JobOutcome::Skipped(_) => return outcome,
+    };
+
+    let mut ledger = ledger.lock().unwrap();
+    ledger.record(job, fingerprint, result);
+    match ledger.save() {
+        Ok(()) => outcome,
+        Err(e) => JobOutcome::Failed(ConversionError::IOError(format!(
+            "could not update job ledger {}: {}",
+            ledger.path().display(),
+            e
+        ))),
+    }
+}
+
+fn convert_job(converter: &VideoToAudioConverter, job: &BatchJob, options: &ConversionOptions) -> JobOutcome {
+    if let Some(dir) = job.output.parent()
+        && let Err(e) = std::fs::create_dir_all(dir)
+    {
+        return JobOutcome::Failed(ConversionError::IOError(e.to_string()));
+    }
+
+    let input = job.input.to_string_lossy();
+    let output = job.output.to_string_lossy();
+    match converter.convert(&input, &output, options, |_| {}) {
+        Ok(()) => JobOutcome::Converted,
+        Err(ConversionError::Cancelled) => JobOutcome::Skipped("batch cancelled".to_string()),
+        Err(e) => JobOutcome::Failed(e),
+    }
+}
+
+fn finish<R: Fn(&BatchResult)>(job: BatchJob, outcome: JobOutcome, elapsed: Duration, on_result: &R) -> BatchResult {
+    let result = BatchResult { job, outcome, elapsed };
+    on_result(&result);
+    result
+}
+
+fn scan_dir(dir: &Path, recursive: bool, files: &mut Vec<PathBuf>) -> Result<(), ConversionError> {
+    let entries = std::fs::read_dir(dir).map_err(|e| ConversionError::IOError(format!("{}: {}", dir.display(), e)))?;
+    for entry in entries {
+        let path = entry.map_err(|e| ConversionError::IOError(e.to_string()))?.path();
+        if path.is_dir() {
+            if recursive {
+                scan_dir(&path, recursive, files)?;
+            }
+        } else if is_media_file(&path) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn is_media_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| MEDIA_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+fn is_glob(source: &str) -> bool {
+    source.contains(['*', '?', '['])
+}
+
+// The directory part of a pattern before its first wildcard component
+fn glob_base(pattern: &str) -> PathBuf {
+    Path::new(pattern)
+        .components()
+        .take_while(|c| !is_glob(&c.as_os_str().to_string_lossy()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture_tree(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("vac_batch_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for file in ["a.mp4", "notes.txt", "season1/ep1.mkv", "season1/extras/ep1b.MOV"] {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, b"").unwrap();
+        }
+        root
+    }
+
+    fn relative_outputs(jobs: &[BatchJob], out: &Path) -> Vec<String> {
+        jobs.iter()
+            .map(|j| j.output.strip_prefix(out).unwrap().to_string_lossy().replace('\\', "/"))
+            .collect()
+    }
+
+    #[test]
+    fn test_collect_directory() {
+        let root = fixture_tree("dir");
+        let out = root.join("out");
+        let source = root.to_string_lossy().into_owned();
+
+        let jobs = collect_jobs(std::slice::from_ref(&source), &out, "mp3", &BatchOptions::new()).unwrap();
+        assert_eq!(relative_outputs(&jobs, &out), ["a.mp3"]);
+
+        let jobs = collect_jobs(&[source], &out, "flac", &BatchOptions::new().recursive(true)).unwrap();
+        assert_eq!(relative_outputs(&jobs, &out), ["a.flac", "season1/ep1.flac", "season1/extras/ep1b.flac"]);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_collect_glob() {
+        let root = fixture_tree("glob");
+        let out = root.join("out");
+        let pattern = root.join("**").join("*.mkv").to_string_lossy().into_owned();
+
+        let jobs = collect_jobs(&[pattern.clone(), pattern], &out, "mp3", &BatchOptions::new()).unwrap();
+        assert_eq!(relative_outputs(&jobs, &out), ["season1/ep1.mp3"]);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_glob_base() {
+        assert_eq!(glob_base("videos/2024/*.mp4"), PathBuf::from("videos/2024"));
+        assert_eq!(glob_base("videos/**/clip?.mkv"), PathBuf::from("videos"));
+        assert_eq!(glob_base("*.mp4"), PathBuf::new());
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::BTreeSet;
+use std::process::Command;
+
+use crate::ConversionError;
+use crate::format::OutputFormat;
+
+// Encoders ffmpeg only runs with `-strict experimental`
+pub(crate) const EXPERIMENTAL_ENCODERS: &[&str] = &["opus"];
+
+/// The encoders and muxers compiled into an ffmpeg build, from
+/// `ffmpeg -encoders` and `ffmpeg -muxers`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    encoders: BTreeSet<String>,
+    muxers: BTreeSet<String>,
+}
+
+impl Capabilities {
+    // Runs `ffmpeg -encoders` and `ffmpeg -muxers`
+    pub(crate) fn query(ffmpeg_path: &str) -> Result<Self, ConversionError> {
+        let list = |what: &str| -> Result<String, ConversionError> {
+            let output = Command::new(ffmpeg_path)
+                .args(["-hide_banner", what])
+                .output()
+                .map_err(|e| ConversionError::FFmpegError(format!("cannot run {} {}: {}", ffmpeg_path, what, e)))?;
+            if !output.status.success() {
+                return Err(ConversionError::FFmpegError(format!("{} {} failed", ffmpeg_path, what)));
+            }
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        };
+        Ok(Self::parse(&list("-encoders")?, &list("-muxers")?))
+    }
+
+    /// Reads the output of `ffmpeg -encoders` and `ffmpeg -muxers`: a legend,
+    /// a line of dashes, then one `<flags> <name> <description>` line each.
+    /// Only audio encoders (flags starting with `A`) are kept.
+    pub fn parse(encoders_output: &str, muxers_output: &str) -> Self {
+        fn names(output: &str, keep: fn(&str) -> bool) -> BTreeSet<String> {
+            output
+                .lines()
+                .skip_while(|line| !is_separator(line))
+                .skip(1)
+                .filter_map(|line| {
+                    let mut fields = line.split_whitespace();
+                    let flags = fields.next()?;
+                    fields.next().filter(|_| keep(flags))
+                })
+                .map(str::to_string)
+                .collect()
+        }
+        Self {
+            encoders: names(encoders_output, |flags| flags.starts_with('A')),
+            muxers: names(muxers_output, |_| true),
+        }
+    }
+
+    /// Whether the build has the encoder called `name`, e.g. `libmp3lame`.
+    pub fn has_encoder(&self, name: &str) -> bool {
+        self.encoders.contains(name)
+    }
+
+    /// Whether the build can write the container called `name`, e.g. `ipod`.
+    pub fn has_muxer(&self, name: &str) -> bool {
+        self.muxers.contains(name)
+    }
+
+    /// Every audio encoder of the build.
+    pub fn encoders(&self) -> impl Iterator<Item = &str> {
+        self.encoders.iter().map(String::as_str)
+    }
+
+    /// Every container the build can write.
+    pub fn muxers(&self) -> impl Iterator<Item = &str> {
+        self.muxers.iter().map(String::as_str)
+    }
+
+    /// The best encoder this build has for `format`, trying
+    /// [`OutputFormat::encoders`] in order.
+    pub fn encoder_for(&self, format: OutputFormat) -> Result<&'static str, ConversionError> {
+        self.first_encoder(format, format.encoders())
+    }
+
+    /// Whether `format` can be written at all: an encoder and the muxer for
+    /// its default extension are available.
+    pub fn supports(&self, format: OutputFormat) -> bool {
+        self.encoder_for(format).is_ok() && self.has_muxer(format.muxer(format.extensions()[0]))
+    }
+
+    pub(crate) fn first_encoder(&self, format: OutputFormat, candidates: &[&'static str]) -> Result<&'static str, ConversionError> {
+        candidates.iter().copied().find(|name| self.has_encoder(name)).ok_or_else(|| {
+            ConversionError::EncoderUnavailable(format!(
+                "this FFmpeg build has no {} encoder (looked for {}); install an FFmpeg built with {}",
+                format,
+                candidates.join(", "),
+                candidates[0]
+            ))
+        })
+    }
+
+    pub(crate) fn check_muxer(&self, format: OutputFormat, muxer: &str) -> Result<(), ConversionError> {
+        match self.has_muxer(muxer) {
+            true => Ok(()),
+            false => Err(ConversionError::EncoderUnavailable(format!(
+                "this FFmpeg build cannot write the '{}' container needed for {} output",
+                muxer, format
+            ))),
+        }
+    }
+}
+
+fn is_separator(line: &str) -> bool {
+    let line = line.trim();
+    !line.is_empty() && line.chars().all(|c| c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Trimmed output of ffmpeg 6.1 and 7.0, whose muxer legend differ
+    const ENCODERS: &str = "Encoders:
+ V..... = Video
+ A..... = Audio
+ ------
+ V....D libx264              libx264 H.264 / AVC / MPEG-4 AVC / MPEG-4 part 10 (codec h264)
+ A....D aac                  AAC (Advanced Audio Coding)
+ A....D libmp3lame           libmp3lame MP3 (MPEG audio layer 3) (codec mp3)
+ A..X.D opus                 Opus
+ A....D flac                 FLAC (Free Lossless Audio Codec)
+";
+    const MUXERS: &str = " File formats:
+ D.. = Demuxing supported
+ .E. = Muxing supported
+ ..d = Is a device
+ ---
+  E  adts            ADTS AAC (Advanced Audio Coding)
+  E  ipod            iPod H.264 MP4 (MPEG-4 Part 14)
+  E  mp3             MP3 (MPEG audio layer 3)
+  E  flac            raw FLAC
+";
+
+    #[test]
+    fn test_parse_lists() {
+        let capabilities = Capabilities::parse(ENCODERS, MUXERS);
+        // Video encoders are left out
+        assert_eq!(capabilities.encoders().collect::<Vec<_>>(), ["aac", "flac", "libmp3lame", "opus"]);
+        assert!(!capabilities.has_encoder("libx264"));
+        assert!(capabilities.has_muxer("ipod"));
+        assert!(!capabilities.has_muxer("Muxing"));
+        assert!(!capabilities.has_encoder("A....D"));
+        assert_eq!(Capabilities::parse("", ""), Capabilities::default());
+    }
+
+    #[test]
+    fn test_encoder_fallback() {
+        let capabilities = Capabilities::parse(ENCODERS, MUXERS);
+        assert_eq!(capabilities.encoder_for(OutputFormat::Aac).unwrap(), "aac");
+        assert_eq!(capabilities.encoder_for(OutputFormat::Opus).unwrap(), "opus");
+        assert_eq!(capabilities.encoder_for(OutputFormat::Mp3).unwrap(), "libmp3lame");
+
+        let error = capabilities.encoder_for(OutputFormat::Vorbis).unwrap_err();
+        assert!(matches!(error, ConversionError::EncoderUnavailable(_)));
+        assert!(error.to_string().contains("install an FFmpeg built with libvorbis"), "{}", error);
+
+        assert!(capabilities.supports(OutputFormat::Aac));
+        assert!(capabilities.supports(OutputFormat::Flac));
+        // An encoder without its container is no use
+        assert!(!capabilities.supports(OutputFormat::Opus));
+    }
+}
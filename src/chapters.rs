@@ -0,0 +1,138 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::ConversionError;
+use crate::probe::{Chapter, MediaInfo};
+use crate::tracks::sanitize;
+
+/// One file written by
+/// [`split_by_chapters`](crate::VideoToAudioConverter::split_by_chapters).
+#[derive(Debug, Clone)]
+pub struct ChapterOutput {
+    /// The chapter of the input it holds.
+    pub chapter: Chapter,
+    /// 1-based position, also written as the track number tag.
+    pub number: usize,
+    /// Where it is written.
+    pub output_path: String,
+}
+
+// Expands an output template for one chapter. Placeholders:
+// {stem} input file name without extension, {chapter} (or {part}) zero-padded
+// chapter number, {title} chapter title ("Chapter <N>" if untitled).
+pub fn render_template(template: &str, input_path: &str, chapter: &Chapter, number: usize, total: usize) -> String {
+    let stem = Path::new(input_path)
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let width = total.to_string().len().max(2);
+    let title = chapter
+        .title
+        .as_deref()
+        .map(sanitize)
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| format!("Chapter {}", number));
+
+    let number = format!("{:0width$}", number, width = width);
+
+    template
+        .replace("{stem}", &stem)
+        .replace("{chapter}", &number)
+        .replace("{part}", &number)
+        .replace("{title}", &title)
+}
+
+pub fn plan_chapters(info: &MediaInfo, input_path: &str, template: &str) -> Result<Vec<ChapterOutput>, ConversionError> {
+    if !info.chapters.iter().any(|c| c.duration_seconds() > 0.0) {
+        return Err(ConversionError::InvalidOptions("input has no chapters to split by".to_string()));
+    }
+    plan_segments(&info.chapters, input_path, template)
+}
+
+// Output files for arbitrary segments of the input, e.g. ones found by silence detection
+pub fn plan_segments(segments: &[Chapter], input_path: &str, template: &str) -> Result<Vec<ChapterOutput>, ConversionError> {
+    let chapters: Vec<&Chapter> = segments.iter().filter(|c| c.duration_seconds() > 0.0).collect();
+
+    let total = chapters.len();
+    let mut seen = HashSet::new();
+    let mut outputs = Vec::new();
+    for (i, chapter) in chapters.into_iter().enumerate() {
+        let output_path = render_template(template, input_path, chapter, i + 1, total);
+        if !seen.insert(output_path.clone()) {
+            return Err(ConversionError::InvalidOptions(format!(
+                "output template '{}' gives several segments the same name '{}'; add {{chapter}} or {{part}}",
+                template, output_path
+            )));
+        }
+        outputs.push(ChapterOutput { chapter: chapter.clone(), number: i + 1, output_path });
+    }
+
+    Ok(outputs)
+}
+
+// Tags each chapter file as one track of an album named after the input
+pub fn metadata_args(output: &ChapterOutput, total: usize, album: &str) -> Vec<String> {
+    let title = output
+        .chapter
+        .title
+        .clone()
+        .unwrap_or_else(|| format!("Chapter {}", output.number));
+
+    vec![
+        "-map_chapters".to_string(), "-1".to_string(),
+        "-metadata".to_string(), format!("title={}", title),
+        "-metadata".to_string(), format!("track={}/{}", output.number, total),
+        "-metadata".to_string(), format!("album={}", album),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn media() -> MediaInfo {
+        MediaInfo::from_json(
+            r#"{
+            "streams": [{"index": 0, "codec_type": "audio"}],
+            "chapters": [
+                {"id": 0, "start_time": "0.000000", "end_time": "60.000000", "tags": {"title": "Intro"}},
+                {"id": 1, "start_time": "60.000000", "end_time": "60.000000", "tags": {"title": "Empty"}},
+                {"id": 2, "start_time": "60.000000", "end_time": "200.500000", "tags": {"title": "Q&A: Part 1/2"}},
+                {"id": 3, "start_time": "200.500000", "end_time": "300.000000"}
+            ],
+            "format": {"format_name": "mov,mp4,m4a,3gp,3g2,mj2", "duration": "300.0"}
+        }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_plan_chapters() {
+        let outputs = plan_chapters(&media(), "talks/keynote.mp4", "out/{stem} {chapter} - {title}.mp3").unwrap();
+        let paths: Vec<&str> = outputs.iter().map(|o| o.output_path.as_str()).collect();
+        assert_eq!(
+            paths,
+            ["out/keynote 01 - Intro.mp3", "out/keynote 02 - Q&A_ Part 1_2.mp3", "out/keynote 03 - Chapter 3.mp3"]
+        );
+        assert_eq!(outputs[1].chapter.start_seconds, 60.0);
+    }
+
+    #[test]
+    fn test_metadata_args() {
+        let outputs = plan_chapters(&media(), "keynote.mp4", "{chapter}.mp3").unwrap();
+        let args = metadata_args(&outputs[2], outputs.len(), "keynote");
+        assert_eq!(
+            args,
+            ["-map_chapters", "-1", "-metadata", "title=Chapter 3", "-metadata", "track=3/3", "-metadata", "album=keynote"]
+        );
+    }
+
+    #[test]
+    fn test_no_chapters() {
+        let info = MediaInfo::from_json(r#"{"streams": [], "format": {"duration": "10.0"}}"#).unwrap();
+        assert!(matches!(plan_chapters(&info, "a.mp4", "{chapter}.mp3"), Err(ConversionError::InvalidOptions(_))));
+        let outputs = plan_chapters(&media(), "a.mp4", "{title}.mp3").unwrap();
+        assert_eq!(outputs.len(), 3);
+        assert!(matches!(plan_chapters(&media(), "a.mp4", "same.mp3"), Err(ConversionError::InvalidOptions(_))));
+    }
+}
@@ -0,0 +1,562 @@
+use std::path::{Path, PathBuf};
+
+use video_audio_converter::batch::{BatchOptions, LEDGER_FILE};
+use video_audio_converter::options::parse_timestamp;
+use video_audio_converter::{
+    ConversionError, ConversionOptions, FfmpegLocator, LoudnessTarget, OutputFormat, SampleFormat, SilenceOptions, StreamSelector,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Convert,
+    Probe,
+    Batch,
+    Split,
+    Analyze,
+}
+
+use Command::*;
+
+const COMMANDS: [Command; 5] = [Convert, Probe, Batch, Split, Analyze];
+
+impl Command {
+    fn name(&self) -> &'static str {
+        match self {
+            Convert => "convert",
+            Probe => "probe",
+            Batch => "batch",
+            Split => "split",
+            Analyze => "analyze",
+        }
+    }
+
+    fn arguments(&self) -> &'static str {
+        match self {
+            Convert => "<input_video> <output_audio>",
+            Probe => "<input>",
+            Batch => "<file|dir|glob>... <output_dir>",
+            Split => "--chapters|--silence <input_video> <output_template>",
+            Analyze => "<input>",
+        }
+    }
+
+    fn summary(&self) -> &'static str {
+        match self {
+            Convert => "Convert a video to an audio file, or extract several audio tracks",
+            Probe => "Show the container, streams and chapters of a file",
+            Batch => "Convert many files, mirroring their folders into an output directory",
+            Split => "Write one file per chapter, or per segment between silent gaps",
+            Analyze => "Measure loudness and find silent gaps",
+        }
+    }
+
+    // Extra paragraph for the command's --help
+    fn notes(&self) -> &'static str {
+        match self {
+            Convert => {
+                "Use - as the input to read from stdin, or as the output to write to stdout\n\
+                 (stdout needs --format). With --all-tracks the output is a template using\n\
+                 {stem}, {index}, {track}, {lang} and {title}."
+            }
+            Batch => "Outputs that already exist are skipped unless --overwrite is given.",
+            Split => {
+                "The output is a template using {stem}, {title} and {chapter} (--chapters)\n\
+                 or {part} (--silence)."
+            }
+            Probe | Analyze => "",
+        }
+    }
+
+    fn accepts_positional(&self, count: usize) -> bool {
+        match self {
+            Convert | Split => count == 2,
+            Batch => count >= 2,
+            Probe | Analyze => count == 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressMode {
+    Bar,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitMode {
+    Chapters,
+    Silence,
+}
+
+pub struct CliArgs {
+    pub command: Command,
+    pub positional: Vec<String>,
+    pub options: ConversionOptions,
+    pub selectors: Vec<StreamSelector>,
+    pub all_tracks: bool,
+    pub split: Option<SplitMode>,
+    pub silence: SilenceOptions,
+    // Also list silent gaps (analyze)
+    pub find_silence: bool,
+    pub batch: BatchOptions,
+    pub progress: ProgressMode,
+    pub progress_fd: Option<u32>,
+    pub verbosity: Verbosity,
+    // Print the probe or analyze result as one JSON object
+    pub json: bool,
+    pub ffmpeg: FfmpegLocator,
+}
+
+impl CliArgs {
+    // Human-readable output moves to stderr when stdout carries JSON or the
+    // converted audio itself
+    pub fn status_to_stderr(&self) -> bool {
+        let json_on_stdout = self.progress == ProgressMode::Json && self.progress_fd.is_none_or(|fd| fd == 1);
+        json_on_stdout || self.json || self.writes_stdout()
+    }
+
+    pub fn writes_stdout(&self) -> bool {
+        self.command == Convert && self.positional.get(1).is_some_and(|output| output == "-")
+    }
+}
+
+pub enum Parsed {
+    Run(Box<CliArgs>),
+    Help(Option<Command>),
+    Version,
+}
+
+struct Flag {
+    long: &'static str,
+    short: Option<char>,
+    // Placeholder shown in help, for flags that take a value
+    value: Option<&'static str>,
+    // Empty for flags kept only for older scripts, which help leaves out
+    help: &'static str,
+    commands: &'static [Command],
+}
+
+const CONVERTING: &[Command] = &[Convert, Batch, Split];
+const READING: &[Command] = &[Convert, Batch, Split, Analyze];
+const SILENCE: &[Command] = &[Split, Analyze];
+const ALL: &[Command] = &COMMANDS;
+
+const fn flag(long: &'static str, short: Option<char>, value: Option<&'static str>, help: &'static str, commands: &'static [Command]) -> Flag {
+    Flag { long, short, value, help, commands }
+}
+
+const FLAGS: &[Flag] = &[
+    flag("format", Some('f'), Some("<name>"), "mp3, aac, opus, vorbis, flac, wav, alac, ac3 (default: from extension)", CONVERTING),
+    flag("bitrate", Some('b'), Some("<kbps>"), "Constant bitrate, e.g. 64 or 320", CONVERTING),
+    flag("quality", Some('q'), Some("<level>"), "VBR quality level (encoder specific)", CONVERTING),
+    flag("sample-rate", Some('r'), Some("<hz>"), "Output sample rate", CONVERTING),
+    flag("channels", Some('c'), Some("<n>"), "Output channel count", CONVERTING),
+    flag("sample-format", Some('F'), Some("<fmt>"), "u8, s16, s24, s32, f32 (lossless formats only)", CONVERTING),
+    flag("compression-level", Some('C'), Some("<n>"), "FLAC 0-12, ALAC 0-2", CONVERTING),
+    flag("stream", Some('s'), Some("<selector>"), "Audio stream: <index>, a:<n>, <language>, title:<regex> or default", READING),
+    flag("all-tracks", Some('a'), None, "Extract every audio stream (or each --stream given) in one pass", &[Convert]),
+    flag("loudness", Some('L'), Some("<LUFS>"), "Two-pass EBU R128 normalization to this integrated loudness", CONVERTING),
+    flag("true-peak", Some('P'), Some("<dBTP>"), "True-peak ceiling for --loudness (default -1.5)", CONVERTING),
+    flag("start", Some('S'), Some("<time>"), "Start of the segment to use (SS, MM:SS or HH:MM:SS)", READING),
+    flag("end", Some('E'), Some("<time>"), "End of the segment", READING),
+    flag("duration", Some('t'), Some("<time>"), "Length of the segment (instead of --end)", READING),
+    flag("chapters", None, None, "Split at the chapters of the input", &[Split]),
+    flag("silence", None, None, "Split at silent gaps (split), or also list them (analyze)", SILENCE),
+    flag("silence-threshold", Some('n'), Some("<dB>"), "Level counted as silence (default -30)", SILENCE),
+    flag("min-silence", Some('m'), Some("<secs>"), "Shortest gap counted as silence (default 0.5)", SILENCE),
+    flag("min-segment", Some('M'), Some("<time>"), "Skip splits that would leave shorter segments", &[Split]),
+    flag("recursive", Some('R'), None, "Descend into subdirectories of directory inputs", &[Batch]),
+    flag("jobs", Some('j'), Some("<n>"), "Conversions to run in parallel (default: CPU count)", &[Batch]),
+    flag("overwrite", Some('y'), None, "Convert even if the output already exists", &[Batch]),
+    flag("ledger", None, Some("<file>"), "Job ledger used to resume the batch (default: <output_dir>/.video_audio_converter.json)", &[Batch]),
+    flag("no-ledger", None, None, "Don't record or resume batch progress", &[Batch]),
+    flag("progress", None, Some("<mode>"), "bar (default) or json: newline-delimited JSON events and a final result", CONVERTING),
+    flag("progress-fd", None, Some("<n>"), "Write JSON progress to this file descriptor instead of stdout", CONVERTING),
+    flag("json", None, None, "Print the result as a JSON object", &[Probe, Analyze]),
+    flag("ffmpeg", None, Some("<path>"), "FFmpeg executable (default: $FFMPEG_PATH, the config file, then PATH)", ALL),
+    flag("ffprobe", None, Some("<path>"), "ffprobe executable (default: $FFPROBE_PATH, the config file, next to FFmpeg)", ALL),
+    flag("verbose", Some('v'), None, "Also show stream details and the full FFmpeg error output", ALL),
+    flag("quiet", None, None, "Only print errors", ALL),
+    flag("help", Some('h'), None, "Show this help", ALL),
+    // Modes from before the subcommands existed, only valid without one
+    flag("batch", None, None, "", ALL),
+    flag("split-chapters", None, None, "", ALL),
+    flag("split-silence", None, None, "", ALL),
+];
+
+fn find_flag(arg: &str) -> Option<&'static Flag> {
+    match arg.strip_prefix("--") {
+        Some(long) => FLAGS.iter().find(|f| f.long == long),
+        None => {
+            let mut chars = arg.strip_prefix('-')?.chars();
+            let short = chars.next().filter(|_| chars.next().is_none())?;
+            FLAGS.iter().find(|f| f.short == Some(short))
+        }
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, ConversionError> {
+    value
+        .parse()
+        .map_err(|_| ConversionError::InvalidOptions(format!("--{} expects a number, got '{}'", flag, value)))
+}
+
+pub fn parse_args(args: &[String]) -> Result<Parsed, ConversionError> {
+    // "--flag=value" is the same as "--flag value"
+    let mut args: Vec<String> = args
+        .iter()
+        .flat_map(|arg| match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => vec![flag.to_string(), value.to_string()],
+            _ => vec![arg.clone()],
+        })
+        .collect();
+
+    let mut legacy = false;
+    let mut command = match args.first().map(String::as_str) {
+        Some("help") => return Ok(Parsed::Help(args.get(1).and_then(|name| COMMANDS.into_iter().find(|c| c.name() == name)))),
+        Some("--version" | "-V") => return Ok(Parsed::Version),
+        Some(name) if COMMANDS.iter().any(|c| c.name() == name) => {
+            let name = args.remove(0);
+            COMMANDS.into_iter().find(|c| c.name() == name).unwrap()
+        }
+        Some("-h" | "--help") | None => return Ok(Parsed::Help(None)),
+        // Without a subcommand the arguments are those of convert
+        Some(_) => {
+            legacy = true;
+            Convert
+        }
+    };
+    let mut split = None;
+    if legacy {
+        let modes: Vec<&String> = args.iter().filter(|a| ["--batch", "--split-chapters", "--split-silence"].contains(&a.as_str())).collect();
+        match modes.as_slice() {
+            [] => {}
+            [mode] if *mode == "--batch" => command = Batch,
+            [mode] if *mode == "--split-chapters" => (command, split) = (Split, Some(SplitMode::Chapters)),
+            [_] => (command, split) = (Split, Some(SplitMode::Silence)),
+            _ => return Err(ConversionError::InvalidOptions("--batch, --split-chapters and --split-silence cannot be combined".to_string())),
+        }
+    }
+
+    let mut positional = Vec::new();
+    let mut options = ConversionOptions::new();
+    let mut selectors = Vec::new();
+    let mut all_tracks = false;
+    let mut silence = SilenceOptions::new();
+    let mut find_silence = false;
+    let mut loudness: Option<f64> = None;
+    let mut true_peak: Option<f64> = None;
+    let mut progress = ProgressMode::Bar;
+    let mut progress_fd = None;
+    let mut verbosity = Verbosity::Normal;
+    let mut json = false;
+    let mut ffmpeg = FfmpegLocator::new();
+    let mut batch = BatchOptions::new();
+    // None: default location inside the output directory
+    let mut ledger_path: Option<Option<PathBuf>> = None;
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        // A lone "-" is stdin or stdout
+        if !arg.starts_with('-') || arg == "-" {
+            positional.push(arg.clone());
+            continue;
+        }
+        let flag = find_flag(arg).ok_or_else(|| ConversionError::InvalidOptions(format!("unknown option '{}'", arg)))?;
+        if !flag.commands.contains(&command) {
+            return Err(ConversionError::InvalidOptions(format!(
+                "'{}' is not an option of {} (see '{} --help')",
+                arg,
+                command.name(),
+                command.name()
+            )));
+        }
+        let value = match flag.value {
+            Some(placeholder) => iter
+                .next()
+                .map(String::as_str)
+                .ok_or_else(|| ConversionError::InvalidOptions(format!("--{} expects {}", flag.long, placeholder)))?,
+            None => "",
+        };
+
+        match flag.long {
+            "format" => options = options.format(value.parse::<OutputFormat>()?),
+            "bitrate" => options = options.bitrate(parse_number(flag.long, value)?),
+            "quality" => options = options.vbr_quality(parse_number(flag.long, value)?),
+            "sample-rate" => options = options.sample_rate(parse_number(flag.long, value)?),
+            "channels" => options = options.channels(parse_number(flag.long, value)?),
+            "sample-format" => options = options.sample_format(value.parse::<SampleFormat>()?),
+            "compression-level" => options = options.compression_level(parse_number(flag.long, value)?),
+            "stream" => selectors.push(value.parse::<StreamSelector>()?),
+            "all-tracks" => all_tracks = true,
+            "loudness" => loudness = Some(parse_number(flag.long, value)?),
+            "true-peak" => true_peak = Some(parse_number(flag.long, value)?),
+            "start" => options = options.start(parse_timestamp(value)?),
+            "end" => options = options.end(parse_timestamp(value)?),
+            "duration" => options = options.duration(parse_timestamp(value)?),
+            "chapters" => split = Some(SplitMode::Chapters),
+            "silence" if command == Analyze => find_silence = true,
+            "silence" => split = Some(SplitMode::Silence),
+            "silence-threshold" => silence = silence.noise_threshold(parse_number(flag.long, value)?),
+            "min-silence" => silence = silence.min_silence(parse_number(flag.long, value)?),
+            "min-segment" => silence = silence.min_segment(parse_timestamp(value)?),
+            "recursive" => batch = batch.recursive(true),
+            "jobs" => batch = batch.jobs(parse_number(flag.long, value)?),
+            "overwrite" => batch = batch.overwrite(true),
+            "ledger" => ledger_path = Some(Some(PathBuf::from(value))),
+            "no-ledger" => ledger_path = Some(None),
+            "progress" => {
+                progress = match value {
+                    "bar" => ProgressMode::Bar,
+                    "json" => ProgressMode::Json,
+                    _ => return Err(ConversionError::InvalidOptions("--progress expects bar or json".to_string())),
+                };
+            }
+            "progress-fd" => progress_fd = Some(parse_number(flag.long, value)?),
+            "json" => json = true,
+            "ffmpeg" => ffmpeg = ffmpeg.ffmpeg_path(value),
+            "ffprobe" => ffmpeg = ffmpeg.ffprobe_path(value),
+            "verbose" => verbosity = Verbosity::Verbose,
+            "quiet" => verbosity = Verbosity::Quiet,
+            "help" => return Ok(Parsed::Help(Some(command))),
+            // Already handled above
+            "batch" | "split-chapters" | "split-silence" if legacy => {}
+            "batch" | "split-chapters" | "split-silence" => {
+                return Err(ConversionError::InvalidOptions(format!("unknown option '{}'", arg)));
+            }
+            _ => unreachable!("flag --{} has no handler", flag.long),
+        }
+    }
+
+    match (loudness, true_peak) {
+        (Some(lufs), Some(db)) => options = options.normalize_loudness(LoudnessTarget::new(lufs).true_peak(db)),
+        (Some(lufs), None) => options = options.normalize_loudness(LoudnessTarget::new(lufs)),
+        (None, Some(_)) => return Err(ConversionError::InvalidOptions("--true-peak requires --loudness".to_string())),
+        (None, None) => {}
+    }
+    if progress_fd.is_some() && progress != ProgressMode::Json {
+        return Err(ConversionError::InvalidOptions("--progress-fd requires --progress json".to_string()));
+    }
+    if command == Split && split.is_none() {
+        return Err(ConversionError::InvalidOptions("split needs --chapters or --silence".to_string()));
+    }
+    if !all_tracks {
+        if selectors.len() > 1 {
+            return Err(ConversionError::InvalidOptions("several --stream selectors need --all-tracks".to_string()));
+        }
+        if let Some(selector) = selectors.pop() {
+            options = options.audio_stream(selector);
+        }
+    }
+    if !command.accepts_positional(positional.len()) {
+        return Err(ConversionError::InvalidOptions(format!(
+            "{} expects {} (see '{} --help')",
+            command.name(),
+            command.arguments(),
+            command.name()
+        )));
+    }
+
+    let default_ledger = || positional.last().map(|root| Path::new(root).join(LEDGER_FILE));
+    let batch = batch.ledger(ledger_path.unwrap_or_else(default_ledger));
+
+    let cli = CliArgs {
+        command,
+        positional,
+        options,
+        selectors,
+        all_tracks,
+        split,
+        silence,
+        find_silence,
+        batch,
+        progress,
+        progress_fd,
+        verbosity,
+        json,
+        ffmpeg,
+    };
+    if cli.writes_stdout() && cli.progress == ProgressMode::Json && cli.progress_fd.is_none_or(|fd| fd == 1) {
+        return Err(ConversionError::InvalidOptions(
+            "stdout carries the audio, send JSON progress elsewhere with --progress-fd".to_string(),
+        ));
+    }
+    Ok(Parsed::Run(Box::new(cli)))
+}
+
+// Exit status for each kind of failure, so scripts can tell them apart
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_BATCH_FAILURES: i32 = 3;
+pub const EXIT_CANCELLED: i32 = 130;
+
+pub fn exit_code(error: &ConversionError) -> i32 {
+    match error {
+        ConversionError::FFmpegFailed(_) | ConversionError::FFmpegError(_) => EXIT_FAILURE,
+        ConversionError::InvalidOptions(_) => EXIT_USAGE,
+        ConversionError::FileNotFound => 4,
+        ConversionError::InvalidFormat(_) => 5,
+        ConversionError::StreamNotFound(_) | ConversionError::NoAudioStream(_) => 6,
+        ConversionError::CorruptInput(_) | ConversionError::InvalidData(_) => 7,
+        ConversionError::UnknownEncoder(_) | ConversionError::EncoderUnavailable(_) => 8,
+        ConversionError::PermissionDenied(_) => 9,
+        ConversionError::DiskFull(_) => 10,
+        ConversionError::IOError(_) => 11,
+        ConversionError::Cancelled => EXIT_CANCELLED,
+    }
+}
+
+const EXIT_CODES: &[(i32, &str)] = &[
+    (0, "Success"),
+    (EXIT_FAILURE, "FFmpeg failed"),
+    (EXIT_USAGE, "Invalid arguments or options"),
+    (EXIT_BATCH_FAILURES, "Some batch jobs failed"),
+    (4, "Input not found"),
+    (5, "Unsupported or mismatched format"),
+    (6, "No matching audio stream"),
+    (7, "Corrupt or unreadable input"),
+    (8, "Encoder not available in this FFmpeg build"),
+    (9, "Permission denied"),
+    (10, "Disk full"),
+    (11, "I/O error"),
+    (EXIT_CANCELLED, "Cancelled with Ctrl-C"),
+];
+
+pub fn help(program: &str, command: Option<Command>) -> String {
+    let mut text = String::new();
+    let mut line = |s: String| {
+        text.push_str(&s);
+        text.push('\n');
+    };
+
+    let Some(command) = command else {
+        line("Extract audio from video files with FFmpeg".to_string());
+        line(String::new());
+        line(format!("Usage: {} <command> [options] <args>...", program));
+        line(format!("       {} [options] <input_video> <output_audio>   (same as convert)", program));
+        line(String::new());
+        line("Commands:".to_string());
+        for command in COMMANDS {
+            line(format!("  {:<10}{}", command.name(), command.summary()));
+        }
+        line(String::new());
+        line("Options:".to_string());
+        for flag in FLAGS.iter().filter(|f| !f.help.is_empty() && f.commands == ALL) {
+            line(flag_help(flag));
+        }
+        line(format!("  -V, --version{:15}Show the version", ""));
+        line(String::new());
+        line("Exit status:".to_string());
+        for (code, meaning) in EXIT_CODES {
+            line(format!("  {:<5}{}", code, meaning));
+        }
+        line(String::new());
+        line(format!("Run '{} <command> --help' for the options of a command.", program));
+        return text;
+    };
+
+    line(command.summary().to_string());
+    line(String::new());
+    line(format!("Usage: {} {} [options] {}", program, command.name(), command.arguments()));
+    if !command.notes().is_empty() {
+        line(String::new());
+        line(command.notes().to_string());
+    }
+    line(String::new());
+    line("Options:".to_string());
+    for flag in FLAGS.iter().filter(|f| !f.help.is_empty() && f.commands.contains(&command)) {
+        line(flag_help(flag));
+    }
+    text
+}
+
+fn flag_help(flag: &Flag) -> String {
+    let short = flag.short.map_or("    ".to_string(), |c| format!("-{}, ", c));
+    let name = format!("{}--{} {}", short, flag.long, flag.value.unwrap_or_default());
+    if name.len() < 27 {
+        format!("  {:<27} {}", name, flag.help)
+    } else {
+        format!("  {}\n  {:<27} {}", name, "", flag.help)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Parsed, ConversionError> {
+        parse_args(&args.split_whitespace().map(str::to_string).collect::<Vec<_>>())
+    }
+
+    fn run(args: &str) -> CliArgs {
+        match parse(args) {
+            Ok(Parsed::Run(cli)) => *cli,
+            Ok(_) => panic!("{}: expected a command to run", args),
+            Err(e) => panic!("{}: {}", args, e),
+        }
+    }
+
+    #[test]
+    fn test_subcommands() {
+        let cli = run("convert -f opus --bitrate=96 -S 1:00 -t 30 in.mkv out.ogg");
+        assert_eq!(cli.command, Convert);
+        assert_eq!(cli.options.format_override(), Some(OutputFormat::Opus));
+        assert_eq!(cli.options.output_duration(None).unwrap(), Some(30.0));
+
+        // No subcommand, and the old mode flags, still work
+        assert_eq!(run("in.mp4 out.mp3").command, Convert);
+        assert_eq!(run("--batch -j 2 videos out").command, Batch);
+        let cli = run("--split-silence -n -40 in.mp4 {part}.mp3");
+        assert_eq!((cli.command, cli.split), (Split, Some(SplitMode::Silence)));
+        assert!(parse("split --batch --chapters in.mp4 {chapter}.mp3").is_err());
+
+        let cli = run("analyze --silence --json -v in.mp4");
+        assert!(cli.find_silence && cli.json);
+        assert_eq!(cli.verbosity, Verbosity::Verbose);
+        assert!(run("convert - - -f mp3 --quiet").status_to_stderr());
+        assert_eq!(run("probe --ffmpeg=/opt/ffmpeg/bin/ffmpeg in.mkv").command, Probe);
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        let cases = [
+            ("convert -j 4 in.mp4 out.mp3", "'-j' is not an option of convert"),
+            ("probe in.mp4 out.mp3", "probe expects <input>"),
+            ("split in.mp4 {chapter}.mp3", "split needs --chapters or --silence"),
+            ("convert --bitrate fast in.mp4 out.mp3", "--bitrate expects a number, got 'fast'"),
+            ("convert in.mp4 out.mp3 --start", "--start expects <time>"),
+            ("convert --frobnicate in.mp4 out.mp3", "unknown option '--frobnicate'"),
+            ("convert -f mp3 --progress json in.mp4 -", "stdout carries the audio"),
+        ];
+        for (args, message) in cases {
+            match parse(args) {
+                Err(e) => {
+                    assert!(e.to_string().contains(message), "{}: {}", args, e);
+                    assert_eq!(exit_code(&e), EXIT_USAGE);
+                }
+                Ok(_) => panic!("{}: expected an error", args),
+            }
+        }
+    }
+
+    #[test]
+    fn test_help() {
+        assert!(matches!(parse(""), Ok(Parsed::Help(None))));
+        assert!(matches!(parse("batch --help"), Ok(Parsed::Help(Some(Batch)))));
+        assert!(matches!(parse("help split"), Ok(Parsed::Help(Some(Split)))));
+
+        let text = help("vac", Some(Batch));
+        assert!(text.contains("Usage: vac batch [options] <file|dir|glob>... <output_dir>"));
+        assert!(text.contains("  -j, --jobs <n>"));
+        assert!(!text.contains("--chapters"));
+        assert!(!text.contains("--split-chapters"));
+        assert!(help("vac", None).contains("  6    No matching audio stream"));
+    }
+}
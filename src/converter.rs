@@ -0,0 +1,863 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::backend::{FfmpegBackend, MediaBackend, MediaProcess, Piping, ProcessExit};
+use crate::cancel::CancellationToken;
+use crate::capabilities::Capabilities;
+use crate::chapters::{self, ChapterOutput};
+use crate::diagnostics;
+use crate::discovery::{FfmpegLocator, FfmpegVersion};
+use crate::error::ConversionError;
+use crate::events::{EventSink, ProgressEvent, ProgressParser, Stage, TrackProgress};
+use crate::loudness::{LoudnessMeasurement, LoudnessTarget};
+use crate::options::ConversionOptions;
+use crate::probe::{MediaInfo, StreamInfo};
+use crate::selection::StreamSelector;
+use crate::silence::{self, SilenceInterval, SilenceOptions};
+use crate::staging::{self, StagedOutput};
+use crate::streaming::{self, Pipes};
+use crate::tracks::{self, TrackOutput};
+
+// Non-progress stderr lines kept from each ffmpeg run
+const STDERR_TAIL_LINES: usize = 100;
+// How often a running ffmpeg is checked for cancellation
+pub(crate) const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Runs ffmpeg and ffprobe to extract audio from video files.
+///
+/// Cheap to share: conversions borrow `&self`, so one converter can serve
+/// several threads.
+#[derive(Debug, Clone)]
+pub struct VideoToAudioConverter {
+    pub(crate) backend: Arc<dyn MediaBackend>,
+    pub(crate) version: Option<FfmpegVersion>,
+    // Queried once, shared by clones
+    pub(crate) capabilities: Arc<Capabilities>,
+}
+
+impl VideoToAudioConverter {
+    /// Locates `ffmpeg` and `ffprobe` as described on [`FfmpegLocator`],
+    /// failing with `FFmpegError` if either is missing or ffmpeg is older
+    /// than [`MIN_FFMPEG_VERSION`](crate::discovery::MIN_FFMPEG_VERSION).
+    ///
+    /// Also lists the encoders and muxers ffmpeg was built with, so each
+    /// conversion can use the best encoder available for its format.
+    pub fn new() -> Result<Self, ConversionError> {
+        Self::with_locator(&FfmpegLocator::new())
+    }
+
+    /// Uses the ffmpeg and ffprobe found by `locator`, e.g. one given an
+    /// explicit path or config file.
+    pub fn with_locator(locator: &FfmpegLocator) -> Result<Self, ConversionError> {
+        let found = locator.locate()?;
+        let ffmpeg_path = found.ffmpeg_path.to_string_lossy().into_owned();
+        let capabilities = Capabilities::query(&ffmpeg_path)?;
+        let backend = FfmpegBackend::new(ffmpeg_path, found.ffprobe_path.to_string_lossy());
+        Ok(Self {
+            backend: Arc::new(backend),
+            version: found.version,
+            capabilities: Arc::new(capabilities),
+        })
+    }
+
+    /// Runs probes and conversions through `backend`, e.g. a
+    /// [`ScriptedBackend`](crate::backend::ScriptedBackend) in tests, with
+    /// the encoders and muxers listed in `capabilities`.
+    pub fn with_backend(backend: impl MediaBackend + 'static, capabilities: Capabilities) -> Self {
+        Self {
+            backend: Arc::new(backend),
+            version: None,
+            capabilities: Arc::new(capabilities),
+        }
+    }
+
+    // Runs `program` in place of ffmpeg and ffprobe, for tests that fake them
+    #[cfg(test)]
+    pub(crate) fn with_program(program: &str) -> Self {
+        Self::with_backend(FfmpegBackend::new(program, program), Capabilities::default())
+    }
+
+    /// The ffmpeg executable, unless a custom backend runs something else.
+    pub fn ffmpeg_path(&self) -> Option<&str> {
+        self.backend.ffmpeg_path()
+    }
+
+    /// The ffprobe executable, unless a custom backend runs something else.
+    pub fn ffprobe_path(&self) -> Option<&str> {
+        self.backend.ffprobe_path()
+    }
+
+    /// The ffmpeg release in use, or `None` for a development build.
+    pub fn ffmpeg_version(&self) -> Option<FfmpegVersion> {
+        self.version
+    }
+
+    /// The encoders and muxers of the ffmpeg build in use.
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
+    /// Reads the container, streams and chapters of `input_path`.
+    pub fn probe(&self, input_path: &str) -> Result<MediaInfo, ConversionError> {
+        self.backend.probe(input_path)
+    }
+
+    /// Length of the input in seconds.
+    pub fn get_video_duration(&self, input_path: &str) -> Result<f64, ConversionError> {
+        self.probe(input_path)?
+            .duration_seconds
+            .ok_or_else(|| ConversionError::InvalidFormat("could not determine input duration".to_string()))
+    }
+
+    /// Converts `input_path` to a single audio file at `output_path`.
+    ///
+    /// Reports Started, stage changes and progress to `listener`, then either
+    /// Finished or Failed. The same goes for the split and extract methods.
+    pub fn convert<F>(&self, input_path: &str, output_path: &str, options: &ConversionOptions, listener: F) -> Result<(), ConversionError>
+    where
+        F: FnMut(&ProgressEvent) + Send + 'static,
+    {
+        let events = EventSink::new(listener);
+        events.outcome(self.do_convert(input_path, output_path, options, &events))
+    }
+
+    pub(crate) fn do_convert(&self, input_path: &str, output_path: &str, options: &ConversionOptions, events: &EventSink) -> Result<(), ConversionError> {
+        let media_info = self.probe(input_path)?;
+        events.emit(ProgressEvent::Started { input_path: input_path.to_string(), media_info: media_info.clone() });
+
+        ensure_parent_dir(output_path)?;
+        let staged = StagedOutput::new(output_path);
+        let duration = match options.loudness_target() {
+            Some(target) => self.convert_normalized(input_path, staged.path(), options, &media_info, target, events)?,
+            None => {
+                let (command, duration) = self.conversion_command(input_path, staged.path(), options, &media_info, &[])?;
+                events.emit(ProgressEvent::StageChanged(Stage::Encoding));
+                self.run_ffmpeg(&command, &[staged.path()], options, ProgressParser::new(duration), events)?;
+                duration
+            }
+        };
+        staged.commit(&*self.backend)?;
+        events.finished(vec![output_path.to_string()], duration);
+        Ok(())
+    }
+
+    // Two-pass loudnorm: measure the input, then encode with a linear gain.
+    // Both passes share one progress bar, each covering half of it. Returns
+    // the length of audio written, if known.
+    fn convert_normalized(
+        &self,
+        input_path: &str,
+        output_path: &str,
+        options: &ConversionOptions,
+        media_info: &MediaInfo,
+        target: &LoudnessTarget,
+        events: &EventSink,
+    ) -> Result<Option<f64>, ConversionError> {
+        let format = options.resolve_format(output_path)?;
+        options.validate(format)?;
+
+        let (analysis, duration) = self.analysis_command(input_path, options, media_info, target)?;
+        let total = duration.map(|d| d * 2.0);
+        events.emit(ProgressEvent::StageChanged(Stage::Analyzing));
+        let stderr = self.run_ffmpeg(&analysis, &[], options, ProgressParser::new(duration).within(0.0, total), events)?;
+        let measured = LoudnessMeasurement::from_stderr(&stderr.join("\n"))?;
+
+        let (command, duration) = self.normalized_command(input_path, output_path, options, media_info, target, &measured)?;
+        events.emit(ProgressEvent::StageChanged(Stage::Encoding));
+        // Without a known length each pass counts from zero
+        let parser = ProgressParser::new(duration).within(duration.unwrap_or(0.0), total);
+        self.run_ffmpeg(&command, &[output_path], options, parser, events)?;
+        Ok(duration)
+    }
+
+    // First loudnorm pass, measuring the stream that will be encoded
+    pub(crate) fn analysis_command(
+        &self,
+        input_path: &str,
+        options: &ConversionOptions,
+        media_info: &MediaInfo,
+        target: &LoudnessTarget,
+    ) -> Result<(Vec<String>, Option<f64>), ConversionError> {
+        let stream = loudness_stream(options, media_info)?;
+        let duration = options.output_duration(media_info.duration_seconds)?;
+        let (seek_args, trim_args) = options.trim_args(media_info.supports_fast_seek())?;
+
+        let mut analysis = seek_args;
+        analysis.extend(["-i", input_path, "-vn"].map(String::from));
+        analysis.extend(trim_args);
+        analysis.extend(["-map", &format!("0:{}", stream.index)].map(String::from));
+        analysis.extend(["-af", &target.analysis_filter()].map(String::from));
+        analysis.extend(["-loglevel", "level+info", "-progress", "pipe:2", "-nostats", "-f", "null", "-"].map(String::from));
+        Ok((analysis, duration))
+    }
+
+    // Second loudnorm pass, encoding with the gain derived from `measured`
+    pub(crate) fn normalized_command(
+        &self,
+        input_path: &str,
+        output_path: &str,
+        options: &ConversionOptions,
+        media_info: &MediaInfo,
+        target: &LoudnessTarget,
+        measured: &LoudnessMeasurement,
+    ) -> Result<(Vec<String>, Option<f64>), ConversionError> {
+        let format = options.resolve_format(output_path)?;
+        let stream = loudness_stream(options, media_info)?;
+
+        // loudnorm resamples to 192 kHz internally, so always pin the output rate
+        let mut filter_args = vec!["-af".to_string(), target.normalization_filter(measured)];
+        if format.is_lossless() && !options.has_sample_rate() {
+            let source_rate = stream.sample_rate.unwrap_or(48000);
+            filter_args.extend(["-ar".to_string(), source_rate.to_string()]);
+        }
+
+        self.conversion_command(input_path, output_path, options, media_info, &filter_args)
+    }
+
+    // Builds the ffmpeg command for a single output and returns it along with
+    // the length of audio it will write, if known
+    pub(crate) fn conversion_command(
+        &self,
+        input_path: &str,
+        output_path: &str,
+        options: &ConversionOptions,
+        media_info: &MediaInfo,
+        extra_args: &[String],
+    ) -> Result<(Vec<String>, Option<f64>), ConversionError> {
+        let format = options.resolve_format(output_path)?;
+        let codec_args = options.encoder_args_for(format, output_path, Some(&self.capabilities))?;
+        self.ffmpeg_command(input_path, output_path, &codec_args, options, Some(media_info), extra_args)
+    }
+
+    // `input` and `output` may be `pipe:0` and `pipe:1`. A piped input cannot
+    // be probed, so `media_info` is None for it.
+    pub(crate) fn ffmpeg_command(
+        &self,
+        input: &str,
+        output: &str,
+        codec_args: &[String],
+        options: &ConversionOptions,
+        media_info: Option<&MediaInfo>,
+        extra_args: &[String],
+    ) -> Result<(Vec<String>, Option<f64>), ConversionError> {
+        let duration = options.output_duration(media_info.and_then(|m| m.duration_seconds))?;
+        let (seek_args, trim_args) = options.trim_args(media_info.is_some_and(MediaInfo::supports_fast_seek))?;
+
+        let stream = match (options.audio_stream_selector(), media_info) {
+            (Some(selector), Some(info)) => Some(format!("0:{}", selector.select(info)?.index)),
+            (Some(selector), None) => Some(selector.map_specifier().ok_or_else(|| {
+                ConversionError::InvalidOptions(format!("{} cannot be selected from piped input, use a stream index", selector))
+            })?),
+            (None, Some(info)) if !info.has_audio() => {
+                return Err(ConversionError::StreamNotFound("input has no audio streams".to_string()));
+            }
+            (None, _) => None,
+        };
+        let stream_map = stream.map(|s| vec!["-map".to_string(), s]).unwrap_or_default();
+
+        let mut command = seek_args;                            // Fast input seek to the segment, if any
+        command.extend(["-i", input, "-vn"].map(String::from)); // No video
+        command.extend(trim_args);                              // Output-side seek where input seeking is unsafe
+        command.extend(stream_map);                             // Selected audio stream, if any
+        command.extend_from_slice(codec_args);                  // Codec, quality, sample rate, channels, container
+        command.extend_from_slice(extra_args);                  // Metadata etc. from the caller
+        command.extend([
+            "-y",                                               // Overwrite output file
+            "-loglevel", "level+info",                          // Tag log lines so warnings can be picked out
+            "-progress", "pipe:2",                              // Progress to stderr
+            "-nostats",                                         // Without the \r stats line mixed into it
+            output,
+        ].map(String::from));
+
+        Ok((command, duration))
+    }
+
+    /// Writes one file per chapter of the input, tagged as numbered tracks.
+    /// Progress is reported against the combined length of all chapters.
+    pub fn split_by_chapters<F>(
+        &self,
+        input_path: &str,
+        output_template: &str,
+        options: &ConversionOptions,
+        listener: F,
+    ) -> Result<Vec<ChapterOutput>, ConversionError>
+    where
+        F: FnMut(&ProgressEvent) + Send + 'static,
+    {
+        let events = EventSink::new(listener);
+        events.outcome(self.do_split_by_chapters(input_path, output_template, options, &events))
+    }
+
+    fn do_split_by_chapters(
+        &self,
+        input_path: &str,
+        output_template: &str,
+        options: &ConversionOptions,
+        events: &EventSink,
+    ) -> Result<Vec<ChapterOutput>, ConversionError> {
+        if options.is_trimmed() {
+            return Err(ConversionError::InvalidOptions("trimming cannot be combined with splitting by chapters".to_string()));
+        }
+        if options.loudness_target().is_some() {
+            return Err(ConversionError::InvalidOptions("loudness normalization is only supported for single conversions".to_string()));
+        }
+
+        let media_info = self.probe(input_path)?;
+        events.emit(ProgressEvent::Started { input_path: input_path.to_string(), media_info: media_info.clone() });
+        let outputs = chapters::plan_chapters(&media_info, input_path, output_template)?;
+        self.write_segments(input_path, &media_info, options, &outputs, events)?;
+
+        Ok(outputs)
+    }
+
+    /// Runs silencedetect over the (selected) audio stream of the input.
+    pub fn detect_silence(
+        &self,
+        input_path: &str,
+        options: &ConversionOptions,
+        silence: &SilenceOptions,
+    ) -> Result<Vec<SilenceInterval>, ConversionError> {
+        silence.validate()?;
+
+        let media_info = self.probe(input_path)?;
+        let duration = media_info
+            .duration_seconds
+            .ok_or_else(|| ConversionError::InvalidFormat("could not determine input duration".to_string()))?;
+        let stream = match options.audio_stream_selector() {
+            Some(selector) => format!("0:{}", selector.select(&media_info)?.index),
+            None if media_info.has_audio() => "0:a:0".to_string(),
+            None => return Err(ConversionError::StreamNotFound("input has no audio streams".to_string())),
+        };
+
+        let args = ["-nostats", "-i", input_path, "-map", &stream, "-af", &silence.filter(), "-f", "null", "-"].map(String::from);
+        let mut process = self.backend.spawn(&args, Piping::default(), &[])?;
+
+        let mut stderr = take_stderr(process.as_mut())?;
+        let reader_thread = thread::spawn(move || {
+            let mut text = String::new();
+            let _ = std::io::Read::read_to_string(&mut stderr, &mut text);
+            text
+        });
+        let status = wait_for(process.as_mut(), options.cancellation_token());
+        let stderr = reader_thread.join().unwrap();
+
+        let status = status?;
+        if !status.success() {
+            let lines: Vec<String> = stderr.lines().map(str::to_string).collect();
+            return Err(diagnostics::classify(status, &lines));
+        }
+
+        Ok(silence::parse_silencedetect(&stderr, duration))
+    }
+
+    /// Measures the integrated loudness, true peak and loudness range of the
+    /// (selected) audio stream, like the first pass of loudness normalization.
+    pub fn measure_loudness<F>(&self, input_path: &str, options: &ConversionOptions, listener: F) -> Result<LoudnessMeasurement, ConversionError>
+    where
+        F: FnMut(&ProgressEvent) + Send + 'static,
+    {
+        let events = EventSink::new(listener);
+        events.outcome(self.do_measure_loudness(input_path, options, &events))
+    }
+
+    fn do_measure_loudness(&self, input_path: &str, options: &ConversionOptions, events: &EventSink) -> Result<LoudnessMeasurement, ConversionError> {
+        let media_info = self.probe(input_path)?;
+        events.emit(ProgressEvent::Started { input_path: input_path.to_string(), media_info: media_info.clone() });
+        // The input figures don't depend on the target, so any valid one will do
+        let target = options.loudness_target().copied().unwrap_or(LoudnessTarget::new(-23.0));
+        let (analysis, duration) = self.analysis_command(input_path, options, &media_info, &target)?;
+        events.emit(ProgressEvent::StageChanged(Stage::Analyzing));
+        let stderr = self.run_ffmpeg(&analysis, &[], options, ProgressParser::new(duration), events)?;
+        let measured = LoudnessMeasurement::from_stderr(&stderr.join("\n"))?;
+        events.finished(Vec::new(), duration);
+        Ok(measured)
+    }
+
+    /// Cuts the input at silent gaps and writes each audible segment to its own
+    /// file. Progress is reported across all segments of the encode pass.
+    pub fn split_by_silence<F>(
+        &self,
+        input_path: &str,
+        output_template: &str,
+        options: &ConversionOptions,
+        silence: &SilenceOptions,
+        listener: F,
+    ) -> Result<(Vec<SilenceInterval>, Vec<ChapterOutput>), ConversionError>
+    where
+        F: FnMut(&ProgressEvent) + Send + 'static,
+    {
+        let events = EventSink::new(listener);
+        events.outcome(self.do_split_by_silence(input_path, output_template, options, silence, &events))
+    }
+
+    fn do_split_by_silence(
+        &self,
+        input_path: &str,
+        output_template: &str,
+        options: &ConversionOptions,
+        silence: &SilenceOptions,
+        events: &EventSink,
+    ) -> Result<(Vec<SilenceInterval>, Vec<ChapterOutput>), ConversionError> {
+        if options.is_trimmed() {
+            return Err(ConversionError::InvalidOptions("trimming cannot be combined with splitting by silence".to_string()));
+        }
+        if options.loudness_target().is_some() {
+            return Err(ConversionError::InvalidOptions("loudness normalization is only supported for single conversions".to_string()));
+        }
+
+        let media_info = self.probe(input_path)?;
+        events.emit(ProgressEvent::Started { input_path: input_path.to_string(), media_info: media_info.clone() });
+        events.emit(ProgressEvent::StageChanged(Stage::DetectingSilence));
+        let silences = self.detect_silence(input_path, options, silence)?;
+        let duration = media_info.duration_seconds.unwrap_or_default();
+
+        let segments = silence.segments(&silences, duration);
+        if segments.is_empty() {
+            return Err(ConversionError::InvalidOptions("input contains nothing but silence".to_string()));
+        }
+        let outputs = chapters::plan_segments(&segments, input_path, output_template)?;
+        self.write_segments(input_path, &media_info, options, &outputs, events)?;
+
+        Ok((silences, outputs))
+    }
+
+    // Encodes each segment with a fast seek into the input, one ffmpeg run per
+    // segment, reporting progress against their combined length
+    fn write_segments(
+        &self,
+        input_path: &str,
+        media_info: &MediaInfo,
+        options: &ConversionOptions,
+        outputs: &[ChapterOutput],
+        events: &EventSink,
+    ) -> Result<(), ConversionError> {
+        let total_duration: f64 = outputs.iter().map(|o| o.chapter.duration_seconds()).sum();
+        let album = media_info
+            .tags
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case("title"))
+            .map(|(_, v)| v.clone())
+            .or_else(|| Path::new(input_path).file_stem().map(|s| s.to_string_lossy().into_owned()))
+            .unwrap_or_default();
+
+        let mut completed = 0.0;
+        let mut staged = Vec::with_capacity(outputs.len());
+
+        for output in outputs {
+            ensure_parent_dir(&output.output_path)?;
+
+            let segment_options = options
+                .clone()
+                .start(output.chapter.start_seconds)
+                .end(output.chapter.end_seconds);
+            let metadata = chapters::metadata_args(output, outputs.len(), &album);
+            let segment = StagedOutput::new(&output.output_path);
+            let (command, duration) =
+                self.conversion_command(input_path, segment.path(), &segment_options, media_info, &metadata)?;
+
+            events.emit(ProgressEvent::StageChanged(Stage::Segment { number: output.number, total: outputs.len() }));
+            let parser = ProgressParser::new(duration).within(completed, Some(total_duration));
+            self.run_ffmpeg(&command, &[segment.path()], options, parser, events)?;
+            staged.push(segment);
+
+            completed += output.chapter.duration_seconds();
+        }
+        // Existing files are only replaced once every segment was written
+        staging::commit_all(staged, &*self.backend)?;
+
+        events.finished(outputs.iter().map(|o| o.output_path.clone()).collect(), Some(total_duration));
+        Ok(())
+    }
+
+    /// Extracts several audio streams into separate files with a single decode of
+    /// the input. Returns the planned outputs in the order they were written.
+    pub fn extract_tracks<F>(
+        &self,
+        input_path: &str,
+        output_template: &str,
+        selectors: &[StreamSelector],
+        options: &ConversionOptions,
+        listener: F,
+    ) -> Result<Vec<TrackOutput>, ConversionError>
+    where
+        F: FnMut(&ProgressEvent) + Send + 'static,
+    {
+        let events = EventSink::new(listener);
+        events.outcome(self.do_extract_tracks(input_path, output_template, selectors, options, &events))
+    }
+
+    fn do_extract_tracks(
+        &self,
+        input_path: &str,
+        output_template: &str,
+        selectors: &[StreamSelector],
+        options: &ConversionOptions,
+        events: &EventSink,
+    ) -> Result<Vec<TrackOutput>, ConversionError> {
+        if options.loudness_target().is_some() {
+            return Err(ConversionError::InvalidOptions("loudness normalization is only supported for single conversions".to_string()));
+        }
+
+        let media_info = self.probe(input_path)?;
+        events.emit(ProgressEvent::Started { input_path: input_path.to_string(), media_info: media_info.clone() });
+        let duration = options.output_duration(media_info.duration_seconds)?;
+        let (seek_args, trim_args) = options.trim_args(media_info.supports_fast_seek())?;
+        let tracks = tracks::plan_tracks(&media_info, input_path, output_template, selectors)?;
+        for track in &tracks {
+            ensure_parent_dir(&track.output_path)?;
+        }
+
+        let staged: Vec<StagedOutput> = tracks.iter().map(|track| StagedOutput::new(&track.output_path)).collect();
+        let mut command = seek_args;
+        command.extend(["-i", input_path, "-y", "-loglevel", "level+info", "-progress", "pipe:2", "-nostats"].map(String::from));
+        for (track, output) in tracks.iter().zip(&staged) {
+            let format = options.resolve_format(&track.output_path)?;
+            command.extend_from_slice(&trim_args);
+            command.extend(["-map".to_string(), format!("0:{}", track.stream.index)]);
+            command.extend(options.encoder_args_for(format, output.path(), Some(&self.capabilities))?);
+            command.push(output.path().to_string());
+        }
+
+        let mut track_progress = Vec::new();
+        for track in &tracks {
+            let stream_duration = track.stream.duration_seconds.or(media_info.duration_seconds);
+            track_progress.push(TrackProgress {
+                stream_index: track.stream.index,
+                output_path: track.output_path.clone(),
+                // A stream shorter than the trim start simply has nothing to write
+                duration_seconds: options.output_duration(stream_duration).unwrap_or(Some(0.0)),
+                percentage: None,
+            });
+        }
+
+        events.emit(ProgressEvent::StageChanged(Stage::Encoding));
+        let paths: Vec<&str> = staged.iter().map(StagedOutput::path).collect();
+        self.run_ffmpeg(&command, &paths, options, ProgressParser::new(duration).tracks(track_progress), events)?;
+        staging::commit_all(staged, &*self.backend)?;
+        events.finished(tracks.iter().map(|t| t.output_path.clone()).collect(), duration);
+        Ok(tracks)
+    }
+
+    // Runs ffmpeg with `-progress pipe:2`, emitting an event per progress block
+    // and warning, and returns the last non-progress lines it wrote to stderr.
+    // `outputs` are the `StagedOutput` paths the run writes, which clean up
+    // after a failed run.
+    fn run_ffmpeg(
+        &self,
+        args: &[String],
+        outputs: &[&str],
+        options: &ConversionOptions,
+        parser: ProgressParser,
+        events: &EventSink,
+    ) -> Result<Vec<String>, ConversionError> {
+        self.run_ffmpeg_piped(args, outputs, options, parser, events, Pipes::default())
+            .map(|(log, _)| log)
+    }
+
+    // Like `run_ffmpeg`, also feeding ffmpeg's stdin from `pipes.input` and
+    // copying its stdout to `pipes.output`. Returns the bytes copied as well.
+    pub(crate) fn run_ffmpeg_piped(
+        &self,
+        args: &[String],
+        outputs: &[&str],
+        options: &ConversionOptions,
+        parser: ProgressParser,
+        events: &EventSink,
+        pipes: Pipes,
+    ) -> Result<(Vec<String>, u64), ConversionError> {
+        let cancel = options.cancellation_token();
+        if cancel.is_some_and(|token| token.is_cancelled()) {
+            return Err(ConversionError::Cancelled);
+        }
+
+        let piping = Piping { stdin: pipes.input.is_some(), stdout: pipes.output.is_some() };
+        let mut process = self.backend.spawn(args, piping, outputs)?;
+
+        let reader = BufReader::new(take_stderr(process.as_mut())?);
+        let stdin = process.take_stdin();
+        let stdout = process.take_stdout();
+        let events = events.clone();
+
+        let (status, log, read, written) = thread::scope(|scope| {
+            let progress_thread = scope.spawn(move || {
+                let mut log = StderrLog::new(parser, events);
+                for line in reader.lines().map_while(Result::ok) {
+                    log.feed(line);
+                }
+                log.into_lines()
+            });
+            let input_thread = pipes.input.zip(stdin).map(|(input, stdin)| scope.spawn(move || streaming::pump_input(input, stdin)));
+            let output_thread = pipes.output.zip(stdout).map(|(output, stdout)| scope.spawn(move || streaming::pump_output(stdout, output)));
+
+            let status = wait_for(process.as_mut(), cancel);
+            let log = progress_thread.join().unwrap();
+            let read = input_thread.map_or(Ok(()), |thread| thread.join().unwrap());
+            let written = output_thread.map_or(Ok(0), |thread| thread.join().unwrap());
+            (status, log, read, written)
+        });
+
+        let status = status?;
+
+        // A failed copy explains the failure better than ffmpeg's broken pipe
+        let written = written.map_err(|e| ConversionError::IOError(format!("could not write output: {}", e)))?;
+        read.map_err(|e| ConversionError::IOError(format!("could not read input: {}", e)))?;
+        if !status.success() {
+            return Err(diagnostics::classify(status, &log));
+        }
+
+        Ok((log, written))
+    }
+}
+
+// What ffmpeg writes to stderr, line by line: progress blocks and warnings
+// become events, and the last other lines are kept for diagnostics
+pub(crate) struct StderrLog {
+    parser: ProgressParser,
+    events: EventSink,
+    tail: VecDeque<String>,
+}
+
+impl StderrLog {
+    pub fn new(parser: ProgressParser, events: EventSink) -> Self {
+        Self { parser, events, tail: VecDeque::with_capacity(STDERR_TAIL_LINES) }
+    }
+
+    pub fn feed(&mut self, line: String) {
+        if let Some(event) = self.parser.feed(&line) {
+            self.events.emit(event);
+        }
+        if ProgressParser::is_progress_line(&line) {
+            return;
+        }
+        if self.tail.len() == STDERR_TAIL_LINES {
+            self.tail.pop_front();
+        }
+        self.tail.push_back(line);
+    }
+
+    pub fn into_lines(self) -> Vec<String> {
+        self.tail.into()
+    }
+}
+
+// Waits for ffmpeg to exit, killing it first if the token gets cancelled.
+// A run that fails after cancellation (e.g. ffmpeg itself got Ctrl-C) also
+// counts as cancelled.
+fn wait_for(process: &mut dyn MediaProcess, cancel: Option<&CancellationToken>) -> Result<ProcessExit, ConversionError> {
+    let Some(token) = cancel else {
+        return process.wait().map_err(|e| ConversionError::IOError(e.to_string()));
+    };
+
+    loop {
+        if let Some(status) = process.try_wait().map_err(|e| ConversionError::IOError(e.to_string()))? {
+            if !status.success() && token.is_cancelled() {
+                return Err(ConversionError::Cancelled);
+            }
+            return Ok(status);
+        }
+        if token.is_cancelled() {
+            let _ = process.kill();
+            let _ = process.wait();
+            return Err(ConversionError::Cancelled);
+        }
+        thread::sleep(CANCEL_POLL_INTERVAL);
+    }
+}
+
+// Backends always pipe stderr, where the log and progress go
+fn take_stderr(process: &mut dyn MediaProcess) -> Result<Box<dyn std::io::Read + Send>, ConversionError> {
+    process
+        .take_stderr()
+        .ok_or_else(|| ConversionError::IOError("ffmpeg's stderr is not piped".to_string()))
+}
+
+// loudnorm measures a single stream: the selected one, or the first audio stream
+fn loudness_stream<'a>(options: &ConversionOptions, media_info: &'a MediaInfo) -> Result<&'a StreamInfo, ConversionError> {
+    match options.audio_stream_selector() {
+        Some(selector) => selector.select(media_info),
+        None => media_info
+            .audio_streams()
+            .next()
+            .ok_or_else(|| ConversionError::StreamNotFound("input has no audio streams".to_string())),
+    }
+}
+
+pub(crate) fn ensure_parent_dir(path: &str) -> Result<(), ConversionError> {
+    match Path::new(path).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => {
+            std::fs::create_dir_all(dir).map_err(|e| ConversionError::IOError(e.to_string()))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{ScriptedBackend, ScriptedRun};
+    use crate::loudness::LoudnessTarget;
+
+    #[test]
+    fn test_invalid_file() {
+        let backend = ScriptedBackend::new();
+        let converter = scripted(&backend);
+        let result = converter.convert("nonexistent.mp4", "output.mp3", &ConversionOptions::new(), |_| {});
+        assert!(matches!(result, Err(ConversionError::FileNotFound)));
+        assert!(backend.commands().is_empty());
+    }
+
+    const PROBE_JSON: &str = r#"{
+        "streams": [
+            {"index": 0, "codec_type": "video", "codec_name": "h264"},
+            {"index": 1, "codec_type": "audio", "codec_name": "aac", "channels": 2, "sample_rate": "48000"}
+        ],
+        "format": {"format_name": "mov,mp4,m4a,3gp,3g2,mj2", "duration": "10.0"}
+    }"#;
+
+    // A build with just what MP3 output needs
+    fn scripted(backend: &ScriptedBackend) -> VideoToAudioConverter {
+        let capabilities = Capabilities::parse(" ------\n A....D libmp3lame  MP3\n", " ---\n  E  mp3  MP3\n");
+        let backend = backend.clone().media("talk.mp4", MediaInfo::from_json(PROBE_JSON).unwrap());
+        VideoToAudioConverter::with_backend(backend, capabilities)
+    }
+
+    fn output_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("vac_converter_{}_{}.mp3", name, std::process::id()));
+        path.to_string_lossy().into_owned()
+    }
+
+    fn collect(events: &std::sync::Mutex<Vec<ProgressEvent>>) -> Vec<ProgressEvent> {
+        events.lock().unwrap().clone()
+    }
+
+    #[test]
+    fn test_scripted_conversion() {
+        let backend = ScriptedBackend::new().run(
+            ScriptedRun::new(
+                "[info] ffmpeg version 6.1\n\
+                 out_time_us=2500000\nspeed=2.0x\nprogress=continue\n\
+                 [warning] Guessed Channel Layout: stereo\n\
+                 out_time_us=10000000\ntotal_size=160000\nspeed=2.5x\nprogress=end\n",
+            )
+            .output(vec![0u8; 16]),
+        );
+        let converter = scripted(&backend);
+        let output = output_path("scripted");
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = events.clone();
+        converter
+            .convert("talk.mp4", &output, &ConversionOptions::new().bitrate(128), move |event| sink.lock().unwrap().push(event.clone()))
+            .unwrap();
+
+        let args = &backend.commands()[0];
+        assert_eq!(args[..3], ["-i", "talk.mp4", "-vn"]);
+        assert!(args.windows(2).any(|pair| pair == ["-acodec", "libmp3lame"]));
+        // ffmpeg writes next to the output and the result is moved into place
+        let temp = args.last().unwrap();
+        assert_ne!(temp, &output);
+        assert_eq!(Path::new(temp).parent(), Path::new(&output).parent());
+        assert!(!Path::new(temp).exists());
+
+        let events = collect(&events);
+        let percentages: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                ProgressEvent::Progress(progress) => progress.percentage,
+                _ => None,
+            })
+            .collect();
+        assert_eq!(percentages, [25.0, 100.0]);
+        assert!(events.iter().any(|event| matches!(event, ProgressEvent::Warning(w) if w.contains("Guessed Channel Layout"))));
+        let Some(ProgressEvent::Finished(stats)) = events.last() else {
+            panic!("expected Finished, got {:?}", events.last());
+        };
+        assert_eq!(stats.output_bytes, 16);
+        assert_eq!(backend.remaining_runs(), 0);
+        let _ = std::fs::remove_file(output);
+    }
+
+    #[test]
+    fn test_scripted_conversion_creates_output_dir() {
+        let backend = ScriptedBackend::new().run(ScriptedRun::new("progress=end\n").output("audio"));
+        let converter = scripted(&backend);
+        let dir = std::env::temp_dir().join(format!("vac_converter_new_dir_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let output = dir.join("nested/talk.mp3").to_string_lossy().into_owned();
+
+        converter.convert("talk.mp4", &output, &ConversionOptions::new(), |_| {}).unwrap();
+        assert_eq!(std::fs::read(&output).unwrap(), b"audio");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_scripted_loudness_passes() {
+        let analysis = "out_time_us=10000000\nprogress=end\n\
+            [Parsed_loudnorm_0 @ 0x5631c0e0c2c0]\n{\n\
+            \t\"input_i\" : \"-27.61\",\n\t\"input_tp\" : \"-4.47\",\n\
+            \t\"input_lra\" : \"18.06\",\n\t\"input_thresh\" : \"-39.20\",\n\
+            \t\"target_offset\" : \"0.58\"\n}\n";
+        let backend = ScriptedBackend::new().run(ScriptedRun::new(analysis)).run(ScriptedRun::new("progress=end\n").output("audio"));
+        let converter = scripted(&backend);
+        let output = output_path("loudness");
+        let options = ConversionOptions::new().normalize_loudness(LoudnessTarget::new(-16.0));
+        converter.convert("talk.mp4", &output, &options, |_| {}).unwrap();
+        assert_eq!(std::fs::read(&output).unwrap(), b"audio");
+        let _ = std::fs::remove_file(output);
+
+        let commands = backend.commands();
+        assert_eq!(commands.len(), 2);
+        assert!(commands[0].ends_with(&["-f".to_string(), "null".to_string(), "-".to_string()]));
+        assert!(commands[1].iter().any(|arg| arg.contains("measured_I=-27.61")), "{:?}", commands[1]);
+        // The stats line would otherwise run into the progress blocks
+        assert!(commands.iter().all(|args| args.iter().any(|arg| arg == "-nostats")));
+    }
+
+    #[test]
+    fn test_scripted_failure_and_missing_input() {
+        let video_only = MediaInfo::from_json(r#"{"streams": [{"index": 0, "codec_type": "video"}]}"#).unwrap();
+        let backend = ScriptedBackend::new()
+            .run(ScriptedRun::new("[error] Error opening output files: Permission denied\n").exit_code(1).output("partial"))
+            // Exits cleanly without writing anything
+            .run(ScriptedRun::new("progress=end\n"))
+            // Or with a file that holds no audio
+            .run(ScriptedRun::new("progress=end\n").output("video").output_media(video_only));
+        let converter = scripted(&backend);
+        let output = output_path("failure");
+        std::fs::write(&output, b"previous").unwrap();
+
+        let result = converter.convert("talk.mp4", &output, &ConversionOptions::new(), |_| {});
+        assert!(matches!(result, Err(ConversionError::PermissionDenied(_))), "{:?}", result);
+        let result = converter.convert("talk.mp4", &output, &ConversionOptions::new(), |_| {});
+        assert!(matches!(result, Err(ConversionError::IOError(_))), "{:?}", result);
+        let result = converter.convert("talk.mp4", &output, &ConversionOptions::new(), |_| {});
+        assert!(matches!(result, Err(ConversionError::IOError(ref m)) if m.contains("without an audio stream")), "{:?}", result);
+
+        // No run touched the previous output or left its temp file behind
+        assert_eq!(std::fs::read(&output).unwrap(), b"previous");
+        assert!(backend.commands().iter().all(|args| !Path::new(args.last().unwrap()).exists()));
+        let _ = std::fs::remove_file(&output);
+
+        let result = converter.convert("missing.mp4", &output_path("missing"), &ConversionOptions::new(), |_| {});
+        assert!(matches!(result, Err(ConversionError::FileNotFound)));
+        assert_eq!(backend.commands().len(), 3);
+    }
+
+    #[test]
+    fn test_scripted_cancellation() {
+        let backend = ScriptedBackend::new().run(ScriptedRun::new("out_time_us=1000000\nprogress=continue\n").output("partial").until_killed());
+        let converter = scripted(&backend);
+        let token = CancellationToken::new();
+        let options = ConversionOptions::new().cancellation(token.clone());
+        let result = converter.convert("talk.mp4", &output_path("cancel"), &options, move |event| {
+            if matches!(event, ProgressEvent::Progress(_)) {
+                token.cancel();
+            }
+        });
+        assert!(matches!(result, Err(ConversionError::Cancelled)), "{:?}", result);
+        assert!(!Path::new(backend.commands()[0].last().unwrap()).exists());
+    }
+}
@@ -0,0 +1,134 @@
+use crate::ConversionError;
+use crate::backend::ProcessExit;
+
+// Lines of ffmpeg's stderr kept in an error for logging
+pub const ERROR_TAIL_LINES: usize = 20;
+
+/// Details of a failed ffmpeg run, carried by the errors that classify it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FfmpegFailure {
+    /// `None` when ffmpeg was killed by a signal.
+    pub exit_code: Option<i32>,
+    /// The stderr line that best explains the failure.
+    pub message: String,
+    /// The last lines ffmpeg wrote to stderr, for logging.
+    pub stderr_tail: Vec<String>,
+}
+
+impl std::fmt::Display for FfmpegFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.exit_code {
+            Some(code) => write!(f, "{} (exit status {})", self.message, code),
+            None => write!(f, "{} (terminated by a signal)", self.message),
+        }
+    }
+}
+
+type Variant = fn(FfmpegFailure) -> ConversionError;
+
+// Checked in order, so a specific cause wins over the generic "Invalid data"
+// ffmpeg usually prints after it
+const RULES: &[(&[&str], Variant)] = &[
+    (&["no space left on device", "disk quota exceeded"], ConversionError::DiskFull),
+    (&["permission denied", "operation not permitted", "read-only file system"], ConversionError::PermissionDenied),
+    (&["unknown encoder", "encoder not found"], ConversionError::UnknownEncoder),
+    (&["does not contain any stream", "matches no streams"], ConversionError::NoAudioStream),
+    (
+        &["moov atom not found", "ebml header parsing failed", "error reading header", "header missing", "corrupt", "truncat"],
+        ConversionError::CorruptInput,
+    ),
+    (&["invalid data found when processing input"], ConversionError::InvalidData),
+];
+
+// Turns a failed ffmpeg run into the most specific error its stderr allows
+pub(crate) fn classify(status: ProcessExit, stderr: &[String]) -> ConversionError {
+    classify_lines(status.code, stderr)
+}
+
+fn classify_lines(exit_code: Option<i32>, stderr: &[String]) -> ConversionError {
+    let lines: Vec<String> = stderr.iter().map(|l| strip_level(l)).filter(|l| !l.is_empty()).collect();
+    let tail = lines[lines.len().saturating_sub(ERROR_TAIL_LINES)..].to_vec();
+    let failure = |message: &str| FfmpegFailure {
+        exit_code,
+        message: message.to_string(),
+        stderr_tail: tail.clone(),
+    };
+
+    for (patterns, variant) in RULES {
+        let matched = lines.iter().rev().find(|line| {
+            let line = line.to_lowercase();
+            patterns.iter().any(|p| line.contains(p))
+        });
+        if let Some(line) = matched {
+            return variant(failure(line));
+        }
+    }
+
+    // Otherwise the last line ffmpeg logged as an error, or just the last line
+    let message = stderr
+        .iter()
+        .rev()
+        .find(|l| l.contains("[error] ") || l.contains("[fatal] "))
+        .map(|l| strip_level(l))
+        .or_else(|| lines.last().cloned())
+        .unwrap_or_else(|| "Conversion failed".to_string());
+    ConversionError::FFmpegFailed(failure(&message))
+}
+
+// Drops the "[error] " style tags added by `-loglevel level+info`
+fn strip_level(line: &str) -> String {
+    let mut line = line.to_string();
+    for level in ["[info] ", "[warning] ", "[error] ", "[fatal] ", "[verbose] "] {
+        line = line.replacen(level, "", 1);
+    }
+    line.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_classify_failures() {
+        let corrupt = lines(
+            "[info] ffmpeg version 6.1\n\
+             [mov,mp4,m4a,3gp,3g2,mj2 @ 0x5581] [error] moov atom not found\n\
+             [in#0 @ 0x5580] [error] Error opening input: Invalid data found when processing input\n\
+             [fatal] Error opening input file broken.mp4.",
+        );
+        let ConversionError::CorruptInput(failure) = classify_lines(Some(1), &corrupt) else {
+            panic!("expected CorruptInput");
+        };
+        assert_eq!(failure.message, "[mov,mp4,m4a,3gp,3g2,mj2 @ 0x5581] moov atom not found");
+        assert_eq!(failure.stderr_tail.len(), 4);
+        assert_eq!(failure.to_string(), "[mov,mp4,m4a,3gp,3g2,mj2 @ 0x5581] moov atom not found (exit status 1)");
+
+        let cases = [
+            ("[fatal] Error opening input file notes.txt.\n[error] Invalid data found when processing input", "InvalidData("),
+            ("[error] Unknown encoder 'libfdk_aac'", "UnknownEncoder("),
+            ("[error] out.mp3: Permission denied", "PermissionDenied("),
+            ("[error] Error writing trailer of out.flac: No space left on device", "DiskFull("),
+            ("[fatal] Stream map '0:a' matches no streams.", "NoAudioStream("),
+        ];
+        for (stderr, variant) in cases {
+            let error = classify_lines(Some(1), &lines(stderr));
+            assert!(format!("{:?}", error).starts_with(variant), "{} -> {:?}", stderr, error);
+        }
+    }
+
+    #[test]
+    fn test_generic_failure() {
+        let stderr: Vec<String> = (0..30).map(|i| format!("[info] line {}", i)).chain(lines("[error] Conversion went wrong\n[info] Exiting")).collect();
+        let ConversionError::FFmpegFailed(failure) = classify_lines(None, &stderr) else {
+            panic!("expected FFmpegFailed");
+        };
+        assert_eq!(failure.message, "Conversion went wrong");
+        assert_eq!(failure.stderr_tail.len(), ERROR_TAIL_LINES);
+        assert_eq!(failure.stderr_tail.last().map(String::as_str), Some("Exiting"));
+        assert!(failure.to_string().ends_with("(terminated by a signal)"));
+    }
+}
@@ -0,0 +1,420 @@
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::error::ConversionError;
+
+/// Environment variable naming the ffmpeg executable to use.
+pub const FFMPEG_PATH_ENV: &str = "FFMPEG_PATH";
+/// Environment variable naming the ffprobe executable to use.
+pub const FFPROBE_PATH_ENV: &str = "FFPROBE_PATH";
+
+/// Oldest ffmpeg release the converter is tested against.
+pub const MIN_FFMPEG_VERSION: FfmpegVersion = FfmpegVersion { major: 4, minor: 0, patch: 0 };
+
+// Searched after PATH, for environments (GUI apps, cron) started with a minimal PATH
+const FALLBACK_DIRS: [&str; 3] = ["/usr/local/bin", "/opt/homebrew/bin", "/usr/bin"];
+
+/// A release version of ffmpeg, as printed by `ffmpeg -version`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FfmpegVersion {
+    /// Major release number.
+    pub major: u32,
+    /// Minor release number.
+    pub minor: u32,
+    /// Patch number, 0 when the version has none.
+    pub patch: u32,
+}
+
+impl FfmpegVersion {
+    /// The version `major.minor.patch`.
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self { major, minor, patch }
+    }
+
+    /// Parses the version from the first line of `ffmpeg -version` (or
+    /// `ffprobe -version`), e.g. `ffmpeg version 6.1.1-3ubuntu5 Copyright ...`
+    /// or `ffmpeg version n7.0`.
+    ///
+    /// Returns `None` for development builds (`N-113684-g...`), which carry
+    /// no release number.
+    pub fn parse(version_output: &str) -> Option<Self> {
+        let pattern = Regex::new(r"^\S+ version n?(\d+)\.(\d+)(?:\.(\d+))?").unwrap();
+        let caps = pattern.captures(version_output.lines().next()?)?;
+        Some(Self {
+            major: caps[1].parse().ok()?,
+            minor: caps[2].parse().ok()?,
+            patch: caps.get(3).map_or(Some(0), |m| m.as_str().parse().ok())?,
+        })
+    }
+}
+
+impl fmt::Display for FfmpegVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.patch {
+            0 => write!(f, "{}.{}", self.major, self.minor),
+            patch => write!(f, "{}.{}.{}", self.major, self.minor, patch),
+        }
+    }
+}
+
+// Contents of the config file, e.g. {"ffmpeg_path": "/opt/ffmpeg/bin/ffmpeg"}
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Config {
+    ffmpeg_path: Option<PathBuf>,
+    ffprobe_path: Option<PathBuf>,
+}
+
+/// The executables found by [`FfmpegLocator::locate`].
+#[derive(Debug, Clone)]
+pub struct FfmpegInstallation {
+    /// The ffmpeg executable.
+    pub ffmpeg_path: PathBuf,
+    /// The ffprobe executable.
+    pub ffprobe_path: PathBuf,
+    /// `None` for development builds.
+    pub version: Option<FfmpegVersion>,
+}
+
+/// Finds ffmpeg and ffprobe and checks that ffmpeg is recent enough.
+///
+/// ffmpeg is taken from the first of these that is set: the path given to
+/// [`ffmpeg_path`](Self::ffmpeg_path), the `FFMPEG_PATH` environment
+/// variable, `ffmpeg_path` in the config file, or else the first `ffmpeg` on
+/// `PATH`. ffprobe is resolved the same way (`FFPROBE_PATH`,
+/// `ffprobe_path`), and otherwise looked for next to ffmpeg before `PATH`.
+///
+/// The config file is JSON, read from [`config_file`](Self::config_file) or
+/// by default from `video_audio_converter/config.json` in the user's config
+/// directory. A path that is configured but doesn't work is an error rather
+/// than a reason to fall back to another ffmpeg.
+#[derive(Debug, Clone)]
+pub struct FfmpegLocator {
+    ffmpeg_path: Option<PathBuf>,
+    ffprobe_path: Option<PathBuf>,
+    config_file: Option<PathBuf>,
+    minimum_version: FfmpegVersion,
+}
+
+impl Default for FfmpegLocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FfmpegLocator {
+    /// A locator using the environment, the default config file and `PATH`.
+    pub fn new() -> Self {
+        Self {
+            ffmpeg_path: None,
+            ffprobe_path: None,
+            config_file: None,
+            minimum_version: MIN_FFMPEG_VERSION,
+        }
+    }
+
+    /// Uses this ffmpeg executable; it must exist and run.
+    pub fn ffmpeg_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.ffmpeg_path = Some(path.into());
+        self
+    }
+
+    /// Uses this ffprobe executable; it must exist and run.
+    pub fn ffprobe_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.ffprobe_path = Some(path.into());
+        self
+    }
+
+    /// Reads this config file instead of the default one. Unlike the default
+    /// file, it must exist.
+    pub fn config_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config_file = Some(path.into());
+        self
+    }
+
+    /// Raises (or lowers) the oldest accepted ffmpeg release from
+    /// [`MIN_FFMPEG_VERSION`].
+    pub fn minimum_version(mut self, version: FfmpegVersion) -> Self {
+        self.minimum_version = version;
+        self
+    }
+
+    /// `video_audio_converter/config.json` under `$XDG_CONFIG_HOME`
+    /// (`~/.config`), or `%APPDATA%` on Windows.
+    pub fn default_config_file() -> Option<PathBuf> {
+        config_file_in(&|name| std::env::var_os(name))
+    }
+
+    /// Finds both executables and checks ffmpeg's version, failing with
+    /// `FFmpegError` if either is missing, broken or too old.
+    pub fn locate(&self) -> Result<FfmpegInstallation, ConversionError> {
+        self.locate_with(&|name| std::env::var_os(name))
+    }
+
+    // `env` stands in for the process environment so tests don't have to modify it
+    fn locate_with(&self, env: &dyn Fn(&str) -> Option<OsString>) -> Result<FfmpegInstallation, ConversionError> {
+        let config = self.read_config(env)?;
+        let search_path = env("PATH");
+
+        let ffmpeg_path = match configured("ffmpeg", &self.ffmpeg_path, FFMPEG_PATH_ENV, &config.ffmpeg_path, env) {
+            Some((path, source)) => check_executable(path, "ffmpeg", &source, search_path.as_deref())?,
+            None => search(&executable_name("ffmpeg"), search_path.as_deref())
+                .ok_or_else(|| ConversionError::FFmpegError("FFmpeg not found in PATH".to_string()))?,
+        };
+        let version = check_version(&ffmpeg_path, "ffmpeg", self.minimum_version)?;
+
+        let ffprobe_path = match configured("ffprobe", &self.ffprobe_path, FFPROBE_PATH_ENV, &config.ffprobe_path, env) {
+            Some((path, source)) => check_executable(path, "ffprobe", &source, search_path.as_deref())?,
+            None => {
+                // ffprobe normally ships next to ffmpeg, so look there first
+                let name = executable_name("ffprobe");
+                let sibling = ffmpeg_path.with_file_name(&name);
+                match sibling.is_file() {
+                    true => sibling,
+                    false => search(&name, search_path.as_deref()).ok_or_else(|| {
+                        ConversionError::FFmpegError(format!(
+                            "ffprobe not found next to {} or in PATH",
+                            ffmpeg_path.display()
+                        ))
+                    })?,
+                }
+            }
+        };
+        check_version(&ffprobe_path, "ffprobe", FfmpegVersion::new(0, 0, 0))?;
+
+        Ok(FfmpegInstallation { ffmpeg_path, ffprobe_path, version })
+    }
+
+    fn read_config(&self, env: &dyn Fn(&str) -> Option<OsString>) -> Result<Config, ConversionError> {
+        let path = match &self.config_file {
+            Some(path) => path.clone(),
+            None => match config_file_in(env) {
+                Some(path) => path,
+                None => return Ok(Config::default()),
+            },
+        };
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && self.config_file.is_none() => return Ok(Config::default()),
+            Err(e) => return Err(ConversionError::IOError(format!("cannot read {}: {}", path.display(), e))),
+        };
+        let mut config: Config = serde_json::from_str(&text)
+            .map_err(|e| ConversionError::InvalidOptions(format!("invalid config file {}: {}", path.display(), e)))?;
+
+        // Relative paths in the file are relative to the file itself
+        let base = path.parent().unwrap_or(Path::new(""));
+        for configured in [&mut config.ffmpeg_path, &mut config.ffprobe_path].into_iter().flatten() {
+            if configured.components().count() > 1 && configured.is_relative() {
+                *configured = base.join(&*configured);
+            }
+        }
+        Ok(config)
+    }
+}
+
+// The first explicitly configured path for `tool`, with where it came from for error messages
+fn configured(
+    tool: &str,
+    explicit: &Option<PathBuf>,
+    env_var: &str,
+    from_config: &Option<PathBuf>,
+    env: &dyn Fn(&str) -> Option<OsString>,
+) -> Option<(PathBuf, String)> {
+    if let Some(path) = explicit {
+        return Some((path.clone(), format!("the {} path given", tool)));
+    }
+    if let Some(path) = env(env_var).filter(|value| !value.is_empty()) {
+        return Some((PathBuf::from(path), env_var.to_string()));
+    }
+    from_config.clone().map(|path| (path, format!("{}_path in the config file", tool)))
+}
+
+// A bare name (`ffmpeg-6`) is looked up on PATH like a shell would, and a
+// directory is taken to contain the tool
+fn check_executable(path: PathBuf, tool: &str, source: &str, search_path: Option<&OsStr>) -> Result<PathBuf, ConversionError> {
+    if path.components().count() == 1
+        && let Some(found) = search(path.as_os_str(), search_path)
+    {
+        return Ok(found);
+    }
+    let path = match path.is_dir() {
+        true => path.join(executable_name(tool)),
+        false => path,
+    };
+    match path.is_file() {
+        true => Ok(path),
+        false => Err(ConversionError::FFmpegError(format!("{} ({}) does not exist", path.display(), source))),
+    }
+}
+
+// Runs `<path> -version` to make sure it is the expected tool and new enough
+fn check_version(path: &Path, tool: &str, minimum: FfmpegVersion) -> Result<Option<FfmpegVersion>, ConversionError> {
+    let output = Command::new(path)
+        .arg("-version")
+        .output()
+        .map_err(|e| ConversionError::FFmpegError(format!("cannot run {}: {}", path.display(), e)))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() || !stdout.starts_with(&format!("{} version", tool)) {
+        return Err(ConversionError::FFmpegError(format!("{} is not {}", path.display(), tool)));
+    }
+
+    let version = FfmpegVersion::parse(&stdout);
+    if let Some(version) = version
+        && version < minimum
+    {
+        return Err(ConversionError::FFmpegError(format!(
+            "{} {} at {} is too old, version {} or newer is required",
+            tool,
+            version,
+            path.display(),
+            minimum
+        )));
+    }
+    Ok(version)
+}
+
+fn search(name: &OsStr, search_path: Option<&OsStr>) -> Option<PathBuf> {
+    let dirs = search_path.map(|paths| std::env::split_paths(paths).collect::<Vec<_>>()).unwrap_or_default();
+    dirs.into_iter()
+        .chain(FALLBACK_DIRS.iter().map(PathBuf::from))
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .map(|dir| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
+
+fn executable_name(tool: &str) -> OsString {
+    match cfg!(windows) {
+        true => format!("{}.exe", tool).into(),
+        false => tool.into(),
+    }
+}
+
+fn config_file_in(env: &dyn Fn(&str) -> Option<OsString>) -> Option<PathBuf> {
+    let non_empty = |name| env(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+    let config_dir = match cfg!(windows) {
+        true => non_empty("APPDATA"),
+        false => non_empty("XDG_CONFIG_HOME").or_else(|| non_empty("HOME").map(|home| home.join(".config"))),
+    };
+    config_dir.map(|dir| dir.join("video_audio_converter").join("config.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_parse_version() {
+        let parse = FfmpegVersion::parse;
+        assert_eq!(parse("ffmpeg version 6.1.1-3ubuntu5 Copyright (c) 2000-2023"), Some(FfmpegVersion::new(6, 1, 1)));
+        assert_eq!(parse("ffmpeg version n7.0 Copyright (c) 2000-2024"), Some(FfmpegVersion::new(7, 0, 0)));
+        assert_eq!(parse("ffprobe version 4.4.2-0ubuntu0.22.04.1\nbuilt with gcc"), Some(FfmpegVersion::new(4, 4, 2)));
+        assert_eq!(parse("ffmpeg version N-113684-g5bd1af8d9f-20240204"), None);
+        assert_eq!(parse("not ffmpeg"), None);
+
+        assert!(FfmpegVersion::new(3, 4, 8) < MIN_FFMPEG_VERSION);
+        assert!(FfmpegVersion::new(4, 0, 1) > MIN_FFMPEG_VERSION);
+        assert_eq!(FfmpegVersion::new(6, 1, 0).to_string(), "6.1");
+        assert_eq!(FfmpegVersion::new(6, 1, 2).to_string(), "6.1.2");
+    }
+
+    // Shell scripts posing as ffmpeg/ffprobe builds
+    #[cfg(unix)]
+    fn fake_tool(dir: &Path, name: &str, version_line: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\necho '{}'\n", version_line)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[cfg(unix)]
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vac_discovery_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_locate_order() {
+        let dir = temp_dir("order");
+        let on_path = dir.join("bin");
+        let custom = dir.join("custom");
+        std::fs::create_dir_all(&on_path).unwrap();
+        std::fs::create_dir_all(&custom).unwrap();
+        fake_tool(&on_path, "ffmpeg", "ffmpeg version 6.0 Copyright");
+        fake_tool(&on_path, "ffprobe", "ffprobe version 6.0 Copyright");
+        let custom_ffmpeg = fake_tool(&custom, "ffmpeg", "ffmpeg version 7.1 Copyright");
+        fake_tool(&custom, "ffprobe", "ffprobe version 7.1 Copyright");
+
+        let mut vars = HashMap::from([("PATH", on_path.clone().into_os_string()), ("HOME", dir.clone().into_os_string())]);
+        let locator = FfmpegLocator::new();
+
+        let found = locator.locate_with(&|name| vars.get(name).cloned()).unwrap();
+        assert_eq!(found.ffmpeg_path, on_path.join("ffmpeg"));
+        assert_eq!(found.ffprobe_path, on_path.join("ffprobe"));
+        assert_eq!(found.version, Some(FfmpegVersion::new(6, 0, 0)));
+
+        // The config file wins over PATH, and ffprobe is taken from next to ffmpeg
+        let config = dir.join(".config/video_audio_converter/config.json");
+        std::fs::create_dir_all(config.parent().unwrap()).unwrap();
+        std::fs::write(&config, r#"{"ffmpeg_path": "../../custom/ffmpeg"}"#).unwrap();
+        let found = locator.locate_with(&|name| vars.get(name).cloned()).unwrap();
+        assert_eq!(found.ffprobe_path, dir.join(".config/video_audio_converter/../../custom/ffprobe"));
+        assert_eq!(found.version, Some(FfmpegVersion::new(7, 1, 0)));
+
+        // The environment wins over the config file, and an explicit path over both
+        std::fs::write(&config, r#"{"ffmpeg_path": "/nonexistent/ffmpeg"}"#).unwrap();
+        vars.insert(FFMPEG_PATH_ENV, custom.clone().into_os_string());
+        let found = locator.locate_with(&|name| vars.get(name).cloned()).unwrap();
+        assert_eq!(found.ffmpeg_path, custom_ffmpeg);
+
+        let found = locator.clone().ffmpeg_path(on_path.join("ffmpeg")).locate_with(&|name| vars.get(name).cloned()).unwrap();
+        assert_eq!(found.ffmpeg_path, on_path.join("ffmpeg"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_locate_errors() {
+        let dir = temp_dir("errors");
+        let old = fake_tool(&dir, "ffmpeg", "ffmpeg version 3.4.8 Copyright");
+        let not_ffmpeg = fake_tool(&dir, "sox", "sox: SoX v14.4.2");
+        let snapshot = fake_tool(&dir, "ffmpeg-git", "ffmpeg version N-113684-g5bd1af8d9f");
+        fake_tool(&dir, "ffprobe", "ffprobe version 3.4.8 Copyright");
+        let env = |_: &str| None;
+
+        let error = FfmpegLocator::new().ffmpeg_path(&old).locate_with(&env).unwrap_err();
+        assert!(error.to_string().contains("ffmpeg 3.4.8 at"), "{}", error);
+        assert!(error.to_string().contains("version 4.0 or newer is required"), "{}", error);
+        let found = FfmpegLocator::new().ffmpeg_path(&old).minimum_version(FfmpegVersion::new(3, 0, 0)).locate_with(&env);
+        assert_eq!(found.unwrap().ffprobe_path, dir.join("ffprobe"));
+
+        // Development builds have no release number and are accepted
+        let found = FfmpegLocator::new().ffmpeg_path(&snapshot).locate_with(&env).unwrap();
+        assert_eq!(found.version, None);
+
+        let error = FfmpegLocator::new().ffmpeg_path(&not_ffmpeg).locate_with(&env).unwrap_err();
+        assert!(error.to_string().ends_with("sox is not ffmpeg"), "{}", error);
+        let error = FfmpegLocator::new().ffmpeg_path(dir.join("missing")).locate_with(&env).unwrap_err();
+        assert!(error.to_string().contains("(the ffmpeg path given) does not exist"), "{}", error);
+
+        let config = dir.join("config.json");
+        std::fs::write(&config, r#"{"ffmpeg": "/usr/bin/ffmpeg"}"#).unwrap();
+        let error = FfmpegLocator::new().config_file(&config).locate_with(&env).unwrap_err();
+        assert!(matches!(error, ConversionError::InvalidOptions(_)));
+        let error = FfmpegLocator::new().config_file(dir.join("missing.json")).locate_with(&env).unwrap_err();
+        assert!(matches!(error, ConversionError::IOError(_)));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
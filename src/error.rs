@@ -0,0 +1,81 @@
+use crate::diagnostics::FfmpegFailure;
+
+/// Why a conversion failed.
+///
+/// The variants holding an [`FfmpegFailure`] are ffmpeg runs that exited with
+/// an error, classified from what ffmpeg printed to stderr.
+#[derive(Debug, Clone)]
+pub enum ConversionError {
+    /// The input file does not exist.
+    FileNotFound,
+    /// The input could not be probed, or the output format is unknown or
+    /// cannot be written where it was asked for.
+    InvalidFormat(String),
+    /// The options contradict each other or the input.
+    InvalidOptions(String),
+    /// No audio stream matches the selection.
+    StreamNotFound(String),
+    /// The ffmpeg build in use has no encoder or muxer for the requested
+    /// output.
+    EncoderUnavailable(String),
+    /// ffmpeg or ffprobe could not be found, started or understood.
+    FFmpegError(String),
+    /// Reading or writing a file or stream failed.
+    IOError(String),
+    /// The conversion was stopped through its [`CancellationToken`](crate::CancellationToken).
+    Cancelled,
+    /// ffmpeg found no audio stream to map.
+    NoAudioStream(FfmpegFailure),
+    /// ffmpeg does not know the requested encoder.
+    UnknownEncoder(FfmpegFailure),
+    /// ffmpeg was not allowed to read the input or write the output.
+    PermissionDenied(FfmpegFailure),
+    /// The input is truncated or damaged.
+    CorruptInput(FfmpegFailure),
+    /// The disk filled up while writing the output.
+    DiskFull(FfmpegFailure),
+    /// ffmpeg could not make sense of the input.
+    InvalidData(FfmpegFailure),
+    /// Any other ffmpeg failure.
+    FFmpegFailed(FfmpegFailure),
+}
+
+impl ConversionError {
+    /// Exit status and stderr tail of the ffmpeg run that failed, for logging.
+    pub fn ffmpeg_failure(&self) -> Option<&FfmpegFailure> {
+        match self {
+            ConversionError::NoAudioStream(failure)
+            | ConversionError::UnknownEncoder(failure)
+            | ConversionError::PermissionDenied(failure)
+            | ConversionError::CorruptInput(failure)
+            | ConversionError::DiskFull(failure)
+            | ConversionError::InvalidData(failure)
+            | ConversionError::FFmpegFailed(failure) => Some(failure),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ConversionError::FileNotFound => write!(f, "Input file not found"),
+            ConversionError::InvalidFormat(msg) => write!(f, "Invalid format: {}", msg),
+            ConversionError::InvalidOptions(msg) => write!(f, "Invalid options: {}", msg),
+            ConversionError::StreamNotFound(msg) => write!(f, "Audio stream not found: {}", msg),
+            ConversionError::EncoderUnavailable(msg) => write!(f, "Encoder not available: {}", msg),
+            ConversionError::FFmpegError(msg) => write!(f, "FFmpeg error: {}", msg),
+            ConversionError::IOError(msg) => write!(f, "IO error: {}", msg),
+            ConversionError::Cancelled => write!(f, "Conversion cancelled"),
+            ConversionError::NoAudioStream(failure) => write!(f, "No audio stream: {}", failure),
+            ConversionError::UnknownEncoder(failure) => write!(f, "Unknown encoder: {}", failure),
+            ConversionError::PermissionDenied(failure) => write!(f, "Permission denied: {}", failure),
+            ConversionError::CorruptInput(failure) => write!(f, "Corrupt input: {}", failure),
+            ConversionError::DiskFull(failure) => write!(f, "Disk full: {}", failure),
+            ConversionError::InvalidData(failure) => write!(f, "Invalid data: {}", failure),
+            ConversionError::FFmpegFailed(failure) => write!(f, "FFmpeg failed: {}", failure),
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
@@ -0,0 +1,364 @@
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::ConversionError;
+use crate::probe::MediaInfo;
+
+/// Progress of one output when several tracks are extracted at once.
+#[derive(Debug, Clone)]
+pub struct TrackProgress {
+    /// Absolute index of the input stream being extracted.
+    pub stream_index: usize,
+    /// Where the track is written.
+    pub output_path: String,
+    /// Length of audio the track will hold, if known.
+    pub duration_seconds: Option<f64>,
+    /// How much of it is done, from 0 to 100, if the length is known.
+    pub percentage: Option<f64>,
+}
+
+/// A progress update, sent each time ffmpeg reports one.
+///
+/// Duration and percentage are `None` when the input length is unknown (live
+/// streams, pipes); processed time, bytes and speed still advance.
+#[derive(Debug, Clone, Default)]
+pub struct ConversionProgress {
+    /// Length of audio the conversion will write.
+    pub duration_seconds: Option<f64>,
+    /// Length of audio written so far.
+    pub processed_seconds: f64,
+    /// How much is done, from 0 to 100.
+    pub percentage: Option<f64>,
+    /// Size of the output currently being written.
+    pub bytes_written: u64,
+    /// Encoding speed as a multiple of real time.
+    pub speed: f64,
+    /// Output bitrate as ffmpeg prints it, e.g. `128.0kbits/s`.
+    pub bitrate: String,
+    /// One entry per output when extracting several tracks at once.
+    pub tracks: Vec<TrackProgress>,
+}
+
+impl ConversionProgress {
+    /// Seconds left at the current speed, when the total length is known.
+    pub fn eta_seconds(&self) -> Option<f64> {
+        let duration = self.duration_seconds?;
+        (self.speed > 0.0).then(|| (duration - self.processed_seconds).max(0.0) / self.speed)
+    }
+}
+
+/// What a conversion is doing, for conversions made of several steps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// First loudnorm pass, measuring the input.
+    Analyzing,
+    /// Looking for silences to split at.
+    DetectingSilence,
+    /// Writing the output.
+    Encoding,
+    /// Writing one of several chapter or silence segments.
+    Segment {
+        /// Position of the segment, from 1.
+        number: usize,
+        /// Number of segments.
+        total: usize,
+    },
+}
+
+impl std::fmt::Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Stage::Analyzing => write!(f, "Analyzing loudness"),
+            Stage::DetectingSilence => write!(f, "Detecting silence"),
+            Stage::Encoding => write!(f, "Encoding"),
+            Stage::Segment { number, total } => write!(f, "Encoding segment {}/{}", number, total),
+        }
+    }
+}
+
+/// Summary of a successful conversion.
+#[derive(Debug, Clone)]
+pub struct ConversionStats {
+    /// Time since the conversion started.
+    pub elapsed: Duration,
+    /// Length of audio written, summed over all segments; `None` if the input
+    /// length was unknown.
+    pub duration_seconds: Option<f64>,
+    /// The files written; empty for output written to a stream.
+    pub outputs: Vec<String>,
+    /// Total size of the output.
+    pub output_bytes: u64,
+}
+
+/// What a conversion reports to its listener.
+///
+/// Each conversion ends with exactly one `Finished` or `Failed`; the other
+/// events follow `Started`, which is sent once the input was probed.
+#[derive(Debug, Clone)]
+pub enum ProgressEvent {
+    /// The input was probed and the conversion is about to start.
+    Started {
+        /// The input as given, or `pipe:0` for a reader.
+        input_path: String,
+        /// What ffprobe found in it; empty for a reader.
+        media_info: MediaInfo,
+    },
+    /// ffmpeg reported its progress.
+    Progress(ConversionProgress),
+    /// The conversion moved on to another step.
+    StageChanged(Stage),
+    /// ffmpeg logged a line at warning level.
+    Warning(String),
+    /// The conversion succeeded.
+    Finished(ConversionStats),
+    /// The conversion failed with this error.
+    Failed(ConversionError),
+}
+
+/// Listener that sends every event to a channel, e.g. to print progress from
+/// another thread. Events are dropped once the receiver is gone.
+pub fn forward_to(sender: Sender<ProgressEvent>) -> impl FnMut(&ProgressEvent) + Send + 'static {
+    move |event| {
+        let _ = sender.send(event.clone());
+    }
+}
+
+type Listener = dyn FnMut(&ProgressEvent) + Send;
+
+// Shared handle to the caller's listener, cloned into ffmpeg reader threads
+#[derive(Clone)]
+pub(crate) struct EventSink {
+    listener: Arc<Mutex<Listener>>,
+    started: Instant,
+}
+
+impl EventSink {
+    pub fn new<F>(listener: F) -> Self
+    where
+        F: FnMut(&ProgressEvent) + Send + 'static,
+    {
+        Self {
+            listener: Arc::new(Mutex::new(listener)),
+            started: Instant::now(),
+        }
+    }
+
+    pub fn emit(&self, event: ProgressEvent) {
+        (self.listener.lock().unwrap())(&event);
+    }
+
+    pub fn finished(&self, outputs: Vec<String>, duration_seconds: Option<f64>) {
+        let output_bytes = outputs
+            .iter()
+            .filter_map(|path| std::fs::metadata(path).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+        self.emit(ProgressEvent::Finished(ConversionStats {
+            elapsed: self.started.elapsed(),
+            duration_seconds,
+            outputs,
+            output_bytes,
+        }));
+    }
+
+    // For output written to a stream, which has no path to list or measure
+    pub fn finished_streamed(&self, output_bytes: u64, duration_seconds: Option<f64>) {
+        self.emit(ProgressEvent::Finished(ConversionStats {
+            elapsed: self.started.elapsed(),
+            duration_seconds,
+            outputs: Vec::new(),
+            output_bytes,
+        }));
+    }
+
+    // Passes a result through, reporting it as Failed if it is an error
+    pub fn outcome<T>(&self, result: Result<T, ConversionError>) -> Result<T, ConversionError> {
+        if let Err(e) = &result {
+            self.emit(ProgressEvent::Failed(e.clone()));
+        }
+        result
+    }
+}
+
+// Collects ffmpeg's `-progress` key=value lines and yields one Progress event
+// per completed block (`progress=continue` or `progress=end`)
+pub(crate) struct ProgressParser {
+    progress: ConversionProgress,
+    run_duration: Option<f64>,
+    offset: f64,
+}
+
+impl ProgressParser {
+    // `duration` is None when the input length is unknown; progress is then
+    // indeterminate and has no percentage
+    pub fn new(duration: Option<f64>) -> Self {
+        Self {
+            progress: ConversionProgress {
+                duration_seconds: duration,
+                ..Default::default()
+            },
+            run_duration: duration,
+            offset: 0.0,
+        }
+    }
+
+    // Reports this run as the part starting at `offset` of an operation made
+    // of several consecutive ffmpeg runs lasting `total` seconds together
+    pub fn within(mut self, offset: f64, total: Option<f64>) -> Self {
+        self.offset = offset;
+        self.progress.duration_seconds = total;
+        self
+    }
+
+    pub fn tracks(mut self, tracks: Vec<TrackProgress>) -> Self {
+        self.progress.tracks = tracks;
+        self
+    }
+
+    pub fn is_progress_line(line: &str) -> bool {
+        progress_pair(line).is_some()
+    }
+
+    pub fn feed(&mut self, line: &str) -> Option<ProgressEvent> {
+        let Some((key, value)) = progress_pair(line) else {
+            return warning_message(line).map(ProgressEvent::Warning);
+        };
+
+        match key {
+            // Both are microseconds, out_time_ms is misnamed by ffmpeg
+            "out_time_us" | "out_time_ms" => {
+                if let Ok(microseconds) = value.parse::<u64>() {
+                    self.set_processed(microseconds as f64 / 1_000_000.0);
+                }
+            }
+            "total_size" => {
+                if let Ok(bytes) = value.parse() {
+                    self.progress.bytes_written = bytes;
+                }
+            }
+            "speed" => {
+                if let Ok(speed) = value.trim_end_matches('x').trim().parse() {
+                    self.progress.speed = speed;
+                }
+            }
+            "bitrate" if value.ends_with("kbits/s") => self.progress.bitrate = value.trim().to_string(),
+            "progress" => return Some(ProgressEvent::Progress(self.progress.clone())),
+            _ => {}
+        }
+        None
+    }
+
+    fn set_processed(&mut self, seconds: f64) {
+        let progress = &mut self.progress;
+        let run_seconds = self.run_duration.map_or(seconds, |duration| seconds.min(duration));
+        progress.processed_seconds = self.offset + run_seconds;
+        progress.percentage = percentage(progress.processed_seconds, progress.duration_seconds);
+        for track in progress.tracks.iter_mut() {
+            track.percentage = percentage(seconds, track.duration_seconds).map(|p| p.min(100.0));
+        }
+    }
+}
+
+fn percentage(processed: f64, duration: Option<f64>) -> Option<f64> {
+    duration.filter(|d| *d > 0.0).map(|d| processed / d * 100.0)
+}
+
+fn progress_pair(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once('=')?;
+    let is_key = !key.is_empty() && key.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_');
+    is_key.then_some((key, value))
+}
+
+// With `-loglevel level+info` ffmpeg tags each line as "[context @ 0x..] [warning] message"
+fn warning_message(line: &str) -> Option<String> {
+    let (context, message) = line.split_once("[warning] ")?;
+    Some(format!("{}{}", context, message).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK: &[&str] = &[
+        "bitrate= 128.0kbits/s",
+        "total_size=262144",
+        "out_time_us=15000000",
+        "out_time_ms=15000000",
+        "out_time=00:00:15.000000",
+        "speed=30.1x",
+    ];
+
+    #[test]
+    fn test_one_event_per_block() {
+        let mut parser = ProgressParser::new(Some(60.0));
+        for line in BLOCK {
+            assert!(ProgressParser::is_progress_line(line));
+            assert!(parser.feed(line).is_none());
+        }
+        let Some(ProgressEvent::Progress(progress)) = parser.feed("progress=continue") else {
+            panic!("expected a progress event");
+        };
+        assert_eq!(progress.processed_seconds, 15.0);
+        assert_eq!(progress.percentage, Some(25.0));
+        assert_eq!(progress.bytes_written, 262144);
+        assert_eq!(progress.speed, 30.1);
+        assert_eq!(progress.bitrate, "128.0kbits/s");
+
+        // Unknown start position and N/A values are ignored
+        assert!(parser.feed("out_time_us=-9223372036854775807").is_none());
+        assert!(parser.feed("speed=N/A").is_none());
+        let Some(ProgressEvent::Progress(progress)) = parser.feed("progress=end") else {
+            panic!("expected a progress event");
+        };
+        assert_eq!(progress.processed_seconds, 15.0);
+    }
+
+    #[test]
+    fn test_unknown_duration() {
+        let mut parser = ProgressParser::new(None);
+        for line in BLOCK {
+            parser.feed(line);
+        }
+        let Some(ProgressEvent::Progress(progress)) = parser.feed("progress=continue") else {
+            panic!("expected a progress event");
+        };
+        assert_eq!(progress.processed_seconds, 15.0);
+        assert_eq!(progress.bytes_written, 262144);
+        assert_eq!(progress.percentage, None);
+        assert_eq!(progress.eta_seconds(), None);
+    }
+
+    #[test]
+    fn test_offset_and_warnings() {
+        let mut parser = ProgressParser::new(Some(30.0)).within(30.0, Some(120.0));
+        for line in BLOCK {
+            parser.feed(line);
+        }
+        let Some(ProgressEvent::Progress(progress)) = parser.feed("progress=continue") else {
+            panic!("expected a progress event");
+        };
+        assert_eq!(progress.processed_seconds, 45.0);
+        assert_eq!(progress.duration_seconds, Some(120.0));
+        assert_eq!(progress.percentage, Some(37.5));
+
+        let warning = parser.feed("[mp3 @ 0x55f1] [warning] Estimating duration from bitrate, this may be inaccurate");
+        assert!(matches!(warning, Some(ProgressEvent::Warning(ref m)) if m == "[mp3 @ 0x55f1] Estimating duration from bitrate, this may be inaccurate"));
+        assert!(parser.feed("[info] Stream mapping:").is_none());
+        assert!(!ProgressParser::is_progress_line("Output #0, mp3, to 'a.mp3':"));
+    }
+
+    #[test]
+    fn test_forward_to_channel() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let events = EventSink::new(forward_to(sender));
+        events.emit(ProgressEvent::StageChanged(Stage::Segment { number: 2, total: 5 }));
+        let _ = events.outcome::<()>(Err(ConversionError::Cancelled));
+        drop(events);
+
+        let received: Vec<ProgressEvent> = receiver.iter().collect();
+        assert!(matches!(received[0], ProgressEvent::StageChanged(Stage::Segment { number: 2, total: 5 })));
+        assert!(matches!(received[1], ProgressEvent::Failed(ConversionError::Cancelled)));
+        assert_eq!(received.len(), 2);
+    }
+}
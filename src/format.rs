@@ -0,0 +1,243 @@
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::ConversionError;
+
+/// An audio codec to convert to, with the containers it can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OutputFormat {
+    /// MPEG-1 Layer III.
+    Mp3,
+    /// AAC, in an M4A/MP4 container or as raw ADTS.
+    Aac,
+    /// Opus, in Ogg or WebM.
+    Opus,
+    /// Vorbis, in Ogg.
+    Vorbis,
+    /// Lossless FLAC.
+    Flac,
+    /// Uncompressed PCM in a WAV file.
+    Wav,
+    /// Lossless Apple audio, in M4A or CAF.
+    Alac,
+    /// Dolby Digital.
+    Ac3,
+}
+
+impl OutputFormat {
+    /// Every format, in the order they are listed to users.
+    pub const ALL: [OutputFormat; 8] = [
+        OutputFormat::Mp3,
+        OutputFormat::Aac,
+        OutputFormat::Opus,
+        OutputFormat::Vorbis,
+        OutputFormat::Flac,
+        OutputFormat::Wav,
+        OutputFormat::Alac,
+        OutputFormat::Ac3,
+    ];
+
+    /// Lowercase name, as accepted by `FromStr` and shown by `Display`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputFormat::Mp3 => "mp3",
+            OutputFormat::Aac => "aac",
+            OutputFormat::Opus => "opus",
+            OutputFormat::Vorbis => "vorbis",
+            OutputFormat::Flac => "flac",
+            OutputFormat::Wav => "wav",
+            OutputFormat::Alac => "alac",
+            OutputFormat::Ac3 => "ac3",
+        }
+    }
+
+    /// ffmpeg encoder passed to `-acodec`.
+    pub fn encoder(&self) -> &'static str {
+        match self {
+            OutputFormat::Mp3 => "libmp3lame",
+            OutputFormat::Aac => "aac",
+            OutputFormat::Opus => "libopus",
+            OutputFormat::Vorbis => "libvorbis",
+            OutputFormat::Flac => "flac",
+            OutputFormat::Wav => "pcm_s16le",
+            OutputFormat::Alac => "alac",
+            OutputFormat::Ac3 => "ac3",
+        }
+    }
+
+    /// Encoders that can produce this format, best first.
+    /// [`encoder`](Self::encoder) is the one every common ffmpeg build has;
+    /// libfdk_aac and aac_at (macOS) are optional, and the native Opus
+    /// encoder is experimental.
+    pub fn encoders(&self) -> &'static [&'static str] {
+        match self {
+            OutputFormat::Aac => &["libfdk_aac", "aac", "aac_at"],
+            OutputFormat::Opus => &["libopus", "opus"],
+            OutputFormat::Mp3 => &["libmp3lame"],
+            OutputFormat::Vorbis => &["libvorbis"],
+            OutputFormat::Flac => &["flac"],
+            OutputFormat::Wav => &["pcm_s16le"],
+            OutputFormat::Alac => &["alac"],
+            OutputFormat::Ac3 => &["ac3"],
+        }
+    }
+
+    /// File extensions this format may be written to. The first one is the
+    /// default.
+    pub fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            OutputFormat::Mp3 => &["mp3"],
+            OutputFormat::Aac => &["m4a", "aac", "mp4"],
+            OutputFormat::Opus => &["opus", "ogg", "webm"],
+            OutputFormat::Vorbis => &["ogg", "oga"],
+            OutputFormat::Flac => &["flac"],
+            OutputFormat::Wav => &["wav"],
+            OutputFormat::Alac => &["m4a", "caf"],
+            OutputFormat::Ac3 => &["ac3"],
+        }
+    }
+
+    /// Whether the format keeps the decoded audio bit for bit.
+    pub fn is_lossless(&self) -> bool {
+        matches!(self, OutputFormat::Flac | OutputFormat::Wav | OutputFormat::Alac)
+    }
+
+    /// The format an extension implies. Ambiguous extensions resolve to the
+    /// most common codec for them: .m4a is AAC (not ALAC) and .ogg is Vorbis
+    /// (not Opus).
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext.to_ascii_lowercase().as_str() {
+            "mp3" => Some(OutputFormat::Mp3),
+            "m4a" | "aac" | "mp4" => Some(OutputFormat::Aac),
+            "opus" | "webm" => Some(OutputFormat::Opus),
+            "ogg" | "oga" => Some(OutputFormat::Vorbis),
+            "flac" => Some(OutputFormat::Flac),
+            "wav" => Some(OutputFormat::Wav),
+            "caf" => Some(OutputFormat::Alac),
+            "ac3" => Some(OutputFormat::Ac3),
+            _ => None,
+        }
+    }
+
+    /// The format implied by the extension of `path`, failing with
+    /// `InvalidFormat` for a missing or unknown extension.
+    pub fn from_path(path: &str) -> Result<Self, ConversionError> {
+        let ext = extension_of(path)?;
+        Self::from_extension(&ext).ok_or_else(|| {
+            ConversionError::InvalidFormat(format!("unsupported output extension '.{}'", ext))
+        })
+    }
+
+    /// Whether this format can be written to a file with extension `ext`.
+    pub fn supports_extension(&self, ext: &str) -> bool {
+        let ext = ext.to_ascii_lowercase();
+        self.extensions().iter().any(|e| *e == ext)
+    }
+
+    /// Container muxer passed to `-f`, chosen from the output extension.
+    pub fn muxer(&self, ext: &str) -> &'static str {
+        match (self, ext.to_ascii_lowercase().as_str()) {
+            (OutputFormat::Aac, "aac") => "adts",
+            (OutputFormat::Aac, "mp4") => "mp4",
+            (OutputFormat::Aac, _) => "ipod",
+            (OutputFormat::Alac, "caf") => "caf",
+            (OutputFormat::Alac, _) => "ipod",
+            (OutputFormat::Opus, "ogg") => "ogg",
+            (OutputFormat::Opus, "webm") => "webm",
+            (OutputFormat::Opus, _) => "opus",
+            (OutputFormat::Mp3, _) => "mp3",
+            (OutputFormat::Vorbis, _) => "ogg",
+            (OutputFormat::Flac, _) => "flac",
+            (OutputFormat::Wav, _) => "wav",
+            (OutputFormat::Ac3, _) => "ac3",
+        }
+    }
+
+    /// Extension of a container that can be written to a pipe. MP4 and CAF
+    /// rewrite their header once encoding ends, so ALAC has none.
+    pub fn stream_extension(&self) -> Option<&'static str> {
+        match self {
+            OutputFormat::Mp3 => Some("mp3"),
+            OutputFormat::Aac => Some("aac"),
+            OutputFormat::Opus => Some("opus"),
+            OutputFormat::Vorbis => Some("ogg"),
+            OutputFormat::Flac => Some("flac"),
+            OutputFormat::Wav => Some("wav"),
+            OutputFormat::Ac3 => Some("ac3"),
+            OutputFormat::Alac => None,
+        }
+    }
+
+    /// Checks that the output path's extension can hold this format.
+    pub fn check_output_path(&self, path: &str) -> Result<(), ConversionError> {
+        let ext = extension_of(path)?;
+        if self.supports_extension(&ext) {
+            Ok(())
+        } else {
+            Err(ConversionError::InvalidFormat(format!(
+                "{} audio cannot be written to a '.{}' file (expected one of: {})",
+                self,
+                ext,
+                self.extensions().join(", ")
+            )))
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = ConversionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "mp3" => Ok(OutputFormat::Mp3),
+            "aac" | "m4a" => Ok(OutputFormat::Aac),
+            "opus" => Ok(OutputFormat::Opus),
+            "vorbis" | "ogg" => Ok(OutputFormat::Vorbis),
+            "flac" => Ok(OutputFormat::Flac),
+            "wav" | "pcm" => Ok(OutputFormat::Wav),
+            "alac" => Ok(OutputFormat::Alac),
+            "ac3" => Ok(OutputFormat::Ac3),
+            _ => Err(ConversionError::InvalidFormat(format!("unknown output format '{}'", s))),
+        }
+    }
+}
+
+fn extension_of(path: &str) -> Result<String, ConversionError> {
+    Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .ok_or_else(|| ConversionError::InvalidFormat(format!("output path '{}' has no extension", path)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer_from_extension() {
+        assert_eq!(OutputFormat::from_path("out.mp3").unwrap(), OutputFormat::Mp3);
+        assert_eq!(OutputFormat::from_path("out.M4A").unwrap(), OutputFormat::Aac);
+        assert_eq!(OutputFormat::from_path("dir/out.flac").unwrap(), OutputFormat::Flac);
+        assert_eq!(OutputFormat::from_path("out.ogg").unwrap(), OutputFormat::Vorbis);
+        assert!(matches!(OutputFormat::from_path("out.xyz"), Err(ConversionError::InvalidFormat(_))));
+        assert!(matches!(OutputFormat::from_path("out"), Err(ConversionError::InvalidFormat(_))));
+    }
+
+    #[test]
+    fn test_mismatched_extension() {
+        assert!(OutputFormat::Alac.check_output_path("out.m4a").is_ok());
+        assert!(OutputFormat::Opus.check_output_path("out.ogg").is_ok());
+        assert!(matches!(
+            OutputFormat::Flac.check_output_path("out.mp3"),
+            Err(ConversionError::InvalidFormat(_))
+        ));
+    }
+}
@@ -0,0 +1,306 @@
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde_json::{Value, json};
+
+use video_audio_converter::batch::{BatchResult, BatchSummary, JobOutcome};
+use video_audio_converter::events::{ProgressEvent, Stage};
+use video_audio_converter::probe::{MediaInfo, StreamKind};
+use video_audio_converter::{ConversionError, LoudnessMeasurement, SilenceInterval};
+
+// Writes newline-delimited JSON for `--progress=json`: one object per event,
+// always ending with exactly one "result" object
+#[derive(Clone)]
+pub struct JsonReporter {
+    out: Arc<Mutex<Box<dyn Write + Send>>>,
+    reported: Arc<AtomicBool>,
+}
+
+impl JsonReporter {
+    pub fn stdout() -> Self {
+        Self::new(Box::new(std::io::stdout()))
+    }
+
+    // Lets wrappers keep stdout for the human-readable output
+    #[cfg(unix)]
+    pub fn to_fd(fd: u32) -> Result<Self, ConversionError> {
+        match fd {
+            1 => Ok(Self::stdout()),
+            2 => Ok(Self::new(Box::new(std::io::stderr()))),
+            _ => {
+                let file = std::fs::OpenOptions::new()
+                    .write(true)
+                    .open(format!("/dev/fd/{}", fd))
+                    .map_err(|e| ConversionError::IOError(format!("cannot write to file descriptor {}: {}", fd, e)))?;
+                Ok(Self::new(Box::new(file)))
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn to_fd(_fd: u32) -> Result<Self, ConversionError> {
+        Err(ConversionError::InvalidOptions("--progress-fd is only supported on Unix".to_string()))
+    }
+
+    fn new(out: Box<dyn Write + Send>) -> Self {
+        Self {
+            out: Arc::new(Mutex::new(out)),
+            reported: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    // A reader that went away must not fail the conversion, so write errors are ignored
+    pub fn write(&self, value: &Value) {
+        let mut out = self.out.lock().unwrap();
+        let _ = writeln!(out, "{}", value);
+        let _ = out.flush();
+    }
+
+    // Writes the final result object unless one was already written
+    pub fn result(&self, value: &Value) {
+        if !self.reported.swap(true, Ordering::SeqCst) {
+            self.write(value);
+        }
+    }
+
+    pub fn listener(&self) -> impl FnMut(&ProgressEvent) + Send + 'static {
+        let reporter = self.clone();
+        let mut stage = Stage::Encoding;
+        move |event| match event {
+            ProgressEvent::Finished(_) | ProgressEvent::Failed(_) => reporter.result(&event_json(event, &mut stage)),
+            _ => reporter.write(&event_json(event, &mut stage)),
+        }
+    }
+}
+
+// `stage` tracks the latest StageChanged so progress lines can repeat it
+pub fn event_json(event: &ProgressEvent, stage: &mut Stage) -> Value {
+    match event {
+        ProgressEvent::Started { input_path, media_info } => json!({
+            "event": "started",
+            "input": input_path,
+            "container": media_info.container,
+            "duration_seconds": media_info.duration_seconds,
+            "audio_streams": media_info.audio_streams().count(),
+        }),
+        ProgressEvent::StageChanged(new_stage) => {
+            *stage = *new_stage;
+            with_stage(json!({"event": "stage"}), stage)
+        }
+        ProgressEvent::Progress(progress) => {
+            // Percentage, duration and ETA are null while the input length is unknown
+            let mut value = json!({
+                "event": "progress",
+                "percentage": progress.percentage.map(|p| p.min(100.0)),
+                "processed_seconds": progress.processed_seconds,
+                "duration_seconds": progress.duration_seconds,
+                "bytes_written": progress.bytes_written,
+                "speed": progress.speed,
+                "bitrate": (!progress.bitrate.is_empty()).then_some(&progress.bitrate),
+                "eta_seconds": progress.eta_seconds(),
+            });
+            if !progress.tracks.is_empty() {
+                value["tracks"] = progress
+                    .tracks
+                    .iter()
+                    .map(|t| json!({"stream_index": t.stream_index, "output": t.output_path, "percentage": t.percentage}))
+                    .collect();
+            }
+            with_stage(value, stage)
+        }
+        ProgressEvent::Warning(message) => json!({"event": "warning", "message": message}),
+        ProgressEvent::Finished(stats) => json!({
+            "event": "result",
+            "success": true,
+            "elapsed_seconds": stats.elapsed.as_secs_f64(),
+            "duration_seconds": stats.duration_seconds,
+            "outputs": stats.outputs,
+            "output_bytes": stats.output_bytes,
+        }),
+        ProgressEvent::Failed(e) => error_json(e),
+    }
+}
+
+pub fn error_json(error: &(dyn std::error::Error + 'static)) -> Value {
+    let conversion_error = error.downcast_ref::<ConversionError>();
+    let mut value = json!({
+        "event": "result",
+        "success": false,
+        "cancelled": matches!(conversion_error, Some(ConversionError::Cancelled)),
+        "error": error.to_string(),
+    });
+    if let Some(failure) = conversion_error.and_then(ConversionError::ffmpeg_failure) {
+        value["exit_status"] = json!(failure.exit_code);
+        value["stderr_tail"] = json!(failure.stderr_tail);
+    }
+    value
+}
+
+// One line per finished batch job
+pub fn job_json(result: &BatchResult) -> Value {
+    let mut value = json!({
+        "event": "job",
+        "input": result.job.input,
+        "output": result.job.output,
+        "elapsed_seconds": result.elapsed.as_secs_f64(),
+    });
+    match &result.outcome {
+        JobOutcome::Converted => value["status"] = json!("converted"),
+        JobOutcome::Skipped(reason) => {
+            value["status"] = json!("skipped");
+            value["reason"] = json!(reason);
+        }
+        JobOutcome::Failed(e) => {
+            value["status"] = json!("failed");
+            value["error"] = json!(e.to_string());
+        }
+    }
+    value
+}
+
+pub fn batch_json(summary: &BatchSummary) -> Value {
+    json!({
+        "event": "result",
+        "success": summary.failed() == 0,
+        "converted": summary.converted(),
+        "skipped": summary.skipped(),
+        "failed": summary.failed(),
+        "elapsed_seconds": summary.elapsed.as_secs_f64(),
+    })
+}
+
+// `probe --json`
+pub fn media_json(info: &MediaInfo) -> Value {
+    let streams: Vec<Value> = info
+        .streams
+        .iter()
+        .map(|stream| {
+            json!({
+                "index": stream.index,
+                "kind": kind_name(stream.kind),
+                "codec": stream.codec,
+                "language": stream.language,
+                "title": stream.title,
+                "channels": stream.channels,
+                "channel_layout": stream.channel_layout,
+                "sample_rate": stream.sample_rate,
+                "bitrate": stream.bitrate,
+                "duration_seconds": stream.duration_seconds,
+                "default": stream.disposition.default,
+                "tags": stream.tags,
+            })
+        })
+        .collect();
+    let chapters: Vec<Value> = info
+        .chapters
+        .iter()
+        .map(|chapter| json!({"start_seconds": chapter.start_seconds, "end_seconds": chapter.end_seconds, "title": chapter.title}))
+        .collect();
+    json!({
+        "container": info.container,
+        "duration_seconds": info.duration_seconds,
+        "bitrate": info.bitrate,
+        "streams": streams,
+        "chapters": chapters,
+        "tags": info.tags,
+    })
+}
+
+// `analyze --json`, with "silences" only when silence detection was asked for
+pub fn analysis_json(loudness: &LoudnessMeasurement, silences: Option<&[SilenceInterval]>) -> Value {
+    let mut value = json!({
+        "loudness": {
+            "integrated_lufs": loudness.input_i,
+            "true_peak_db": loudness.input_tp,
+            "loudness_range_lu": loudness.input_lra,
+            "threshold_lufs": loudness.input_thresh,
+        },
+    });
+    if let Some(silences) = silences {
+        value["silences"] = silences
+            .iter()
+            .map(|s| json!({"start_seconds": s.start_seconds, "end_seconds": s.end_seconds}))
+            .collect();
+    }
+    value
+}
+
+pub fn kind_name(kind: StreamKind) -> &'static str {
+    match kind {
+        StreamKind::Audio => "audio",
+        StreamKind::Video => "video",
+        StreamKind::Subtitle => "subtitle",
+        StreamKind::Data => "data",
+        StreamKind::Attachment => "attachment",
+        StreamKind::Unknown => "unknown",
+    }
+}
+
+fn with_stage(mut value: Value, stage: &Stage) -> Value {
+    let name = match stage {
+        Stage::Analyzing => "analyzing",
+        Stage::DetectingSilence => "detecting_silence",
+        Stage::Encoding => "encoding",
+        Stage::Segment { .. } => "segment",
+    };
+    value["stage"] = json!(name);
+    if let Stage::Segment { number, total } = stage {
+        value["segment"] = json!({"number": number, "total": total});
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use video_audio_converter::events::{ConversionProgress, ConversionStats};
+    use std::time::Duration;
+
+    #[test]
+    fn test_progress_lines() {
+        let mut stage = Stage::Encoding;
+        let line = event_json(&ProgressEvent::StageChanged(Stage::Segment { number: 2, total: 5 }), &mut stage);
+        assert_eq!(line.to_string(), r#"{"event":"stage","segment":{"number":2,"total":5},"stage":"segment"}"#);
+
+        let progress = ConversionProgress {
+            duration_seconds: Some(100.0),
+            processed_seconds: 40.0,
+            percentage: Some(40.0),
+            bytes_written: 640_000,
+            speed: 2.0,
+            bitrate: "128.0kbits/s".to_string(),
+            tracks: Vec::new(),
+        };
+        let line = event_json(&ProgressEvent::Progress(progress), &mut stage);
+        assert_eq!(line["eta_seconds"], 30.0);
+        assert_eq!(line["stage"], "segment");
+        assert_eq!(line["bitrate"], "128.0kbits/s");
+
+        let line = event_json(&ProgressEvent::Progress(ConversionProgress { speed: 2.0, ..Default::default() }), &mut stage);
+        assert!(line["eta_seconds"].is_null());
+        assert!(line["percentage"].is_null());
+        assert!(line["bitrate"].is_null());
+    }
+
+    #[test]
+    fn test_result_objects() {
+        let mut stage = Stage::Encoding;
+        let stats = ConversionStats {
+            elapsed: Duration::from_millis(1500),
+            duration_seconds: Some(60.0),
+            outputs: vec!["a.mp3".to_string()],
+            output_bytes: 960_000,
+        };
+        let line = event_json(&ProgressEvent::Finished(stats), &mut stage);
+        assert_eq!(
+            line.to_string(),
+            r#"{"duration_seconds":60.0,"elapsed_seconds":1.5,"event":"result","output_bytes":960000,"outputs":["a.mp3"],"success":true}"#
+        );
+
+        let line = event_json(&ProgressEvent::Failed(ConversionError::Cancelled), &mut stage);
+        assert_eq!(line["success"], false);
+        assert_eq!(line["cancelled"], true);
+        assert_eq!(error_json(&ConversionError::FileNotFound)["error"], "Input file not found");
+    }
+}
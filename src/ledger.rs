@@ -0,0 +1,185 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ConversionError;
+use crate::batch::BatchJob;
+
+/// Written into the batch output directory unless another path is given.
+pub const LEDGER_FILE: &str = ".video_audio_converter.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Completed,
+    Failed,
+}
+
+// Size and modification time identify an input without reading it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    pub size: u64,
+    pub modified_secs: u64,
+    pub modified_nanos: u32,
+}
+
+impl Fingerprint {
+    pub fn of(path: &Path) -> Result<Self, ConversionError> {
+        let metadata = std::fs::metadata(path).map_err(|e| ConversionError::IOError(format!("{}: {}", path.display(), e)))?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .unwrap_or_default();
+        Ok(Self {
+            size: metadata.len(),
+            modified_secs: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub state: JobState,
+    pub input: Fingerprint,
+    pub output: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    // Seconds since the Unix epoch
+    pub updated: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LedgerStatus {
+    // Not seen by an earlier run
+    New,
+    // Converted before and nothing changed since
+    Completed,
+    // The last attempt failed
+    Failed,
+    // The input was modified, or the output moved or disappeared
+    Changed,
+}
+
+#[derive(Debug)]
+pub struct Ledger {
+    path: PathBuf,
+    entries: BTreeMap<PathBuf, LedgerEntry>,
+}
+
+impl Ledger {
+    // Loads the ledger at `path`, starting empty if there is none yet
+    pub fn open(path: &Path) -> Result<Self, ConversionError> {
+        let entries = match std::fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|e| ConversionError::InvalidFormat(format!("unreadable job ledger {}: {}", path.display(), e)))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(ConversionError::IOError(format!("{}: {}", path.display(), e))),
+        };
+        Ok(Self { path: path.to_path_buf(), entries })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn status(&self, job: &BatchJob) -> LedgerStatus {
+        let Some(entry) = self.entries.get(&job.input) else {
+            return LedgerStatus::New;
+        };
+        let unchanged = Fingerprint::of(&job.input).is_ok_and(|f| f == entry.input)
+            && entry.output == job.output
+            && job.output.exists();
+        match entry.state {
+            JobState::Failed => LedgerStatus::Failed,
+            JobState::Completed if unchanged => LedgerStatus::Completed,
+            JobState::Completed => LedgerStatus::Changed,
+        }
+    }
+
+    // `input` is the fingerprint taken before the conversion started, so an
+    // input edited while it was being converted is picked up by the next run
+    pub fn record(&mut self, job: &BatchJob, input: Fingerprint, result: Result<(), &ConversionError>) {
+        let updated = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let entry = LedgerEntry {
+            state: if result.is_ok() { JobState::Completed } else { JobState::Failed },
+            input,
+            output: job.output.clone(),
+            error: result.err().map(|e| e.to_string()),
+            updated,
+        };
+        self.entries.insert(job.input.clone(), entry);
+    }
+
+    // Writes a sibling temp file and renames it over the ledger so an
+    // interrupted save never leaves a truncated file behind
+    pub fn save(&self) -> Result<(), ConversionError> {
+        let json = serde_json::to_string_pretty(&self.entries).map_err(|e| ConversionError::IOError(e.to_string()))?;
+        if let Some(dir) = self.path.parent()
+            && !dir.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(dir).map_err(|e| ConversionError::IOError(e.to_string()))?;
+        }
+        let temp = self.path.with_extension("json.tmp");
+        std::fs::write(&temp, json).map_err(|e| ConversionError::IOError(e.to_string()))?;
+        std::fs::rename(&temp, &self.path).map_err(|e| ConversionError::IOError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vac_ledger_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_status_round_trip() {
+        let dir = temp_dir("status");
+        let job = BatchJob { input: dir.join("a.mp4"), output: dir.join("out/a.mp3") };
+        std::fs::write(&job.input, b"video").unwrap();
+        let ledger_path = dir.join("out").join(LEDGER_FILE);
+
+        let mut ledger = Ledger::open(&ledger_path).unwrap();
+        assert_eq!(ledger.status(&job), LedgerStatus::New);
+
+        ledger.record(&job, Fingerprint::of(&job.input).unwrap(), Err(&ConversionError::FFmpegError("boom".to_string())));
+        ledger.save().unwrap();
+        let mut ledger = Ledger::open(&ledger_path).unwrap();
+        assert_eq!(ledger.status(&job), LedgerStatus::Failed);
+        assert_eq!(ledger.entries[&job.input].error.as_deref(), Some("FFmpeg error: boom"));
+
+        std::fs::write(&job.output, b"audio").unwrap();
+        ledger.record(&job, Fingerprint::of(&job.input).unwrap(), Ok(()));
+        ledger.save().unwrap();
+        let ledger = Ledger::open(&ledger_path).unwrap();
+        assert_eq!(ledger.status(&job), LedgerStatus::Completed);
+
+        // Edited input, or a different output path (e.g. a new --format)
+        std::fs::write(&job.input, b"longer video").unwrap();
+        assert_eq!(ledger.status(&job), LedgerStatus::Changed);
+        let moved = BatchJob { output: dir.join("out/a.flac"), ..job.clone() };
+        assert_eq!(ledger.status(&moved), LedgerStatus::Changed);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_corrupt_ledger() {
+        let dir = temp_dir("corrupt");
+        let path = dir.join(LEDGER_FILE);
+        std::fs::write(&path, "{ not json").unwrap();
+        assert!(matches!(Ledger::open(&path), Err(ConversionError::InvalidFormat(_))));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
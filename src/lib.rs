@@ -0,0 +1,82 @@
+//! Extract audio from video files with FFmpeg.
+//!
+//! [`VideoToAudioConverter`] finds `ffmpeg` and `ffprobe` (see
+//! [`FfmpegLocator`] for where it looks) and runs conversions
+//! configured through [`ConversionOptions`]. Every conversion reports its
+//! progress as a stream of [`ProgressEvent`]s and fails with a
+//! [`ConversionError`] describing what went wrong.
+//!
+//! ```no_run
+//! use video_audio_converter::{ConversionOptions, OutputFormat, ProgressEvent, VideoToAudioConverter};
+//!
+//! # fn main() -> Result<(), video_audio_converter::ConversionError> {
+//! let converter = VideoToAudioConverter::new()?;
+//! let options = ConversionOptions::new().format(OutputFormat::Opus).bitrate(96);
+//! converter.convert("talk.mp4", "talk.opus", &options, |event| {
+//!     if let ProgressEvent::Progress(progress) = event {
+//!         println!("{:.1}s processed", progress.processed_seconds);
+//!     }
+//! })?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Beyond single files the converter can extract every audio track
+//! ([`VideoToAudioConverter::extract_tracks`]), split by chapters or silence,
+//! and [`batch`] converts whole directory trees on a worker pool.
+//!
+//! With the `async` feature, [`VideoToAudioConverter::convert_async`] runs a
+//! conversion from tokio and streams its events without blocking the runtime.
+//!
+//! FFmpeg runs behind the [`backend::MediaBackend`] trait;
+//! [`backend::ScriptedBackend`] replays recorded runs so code using the
+//! converter can be tested without FFmpeg installed.
+
+#![warn(missing_docs)]
+
+#[cfg(feature = "async")]
+mod async_convert;
+/// Where ffmpeg runs: real processes or scripted replays for tests.
+pub mod backend;
+/// Converting whole directory trees on a pool of worker threads.
+pub mod batch;
+mod cancel;
+mod capabilities;
+mod chapters;
+mod converter;
+mod diagnostics;
+/// Finding the ffmpeg and ffprobe executables and their versions.
+pub mod discovery;
+mod error;
+/// Progress events reported while a conversion runs.
+pub mod events;
+mod format;
+mod ledger;
+mod loudness;
+/// Settings for a conversion.
+pub mod options;
+/// What ffprobe reports about an input: streams, chapters and tags.
+pub mod probe;
+mod selection;
+mod silence;
+mod staging;
+mod streaming;
+mod tracks;
+
+#[cfg(feature = "async")]
+pub use async_convert::ProgressStream;
+pub use cancel::CancellationToken;
+pub use capabilities::Capabilities;
+pub use chapters::ChapterOutput;
+pub use converter::VideoToAudioConverter;
+pub use diagnostics::FfmpegFailure;
+pub use discovery::{FfmpegLocator, FfmpegVersion};
+pub use error::ConversionError;
+pub use events::{ConversionProgress, ConversionStats, ProgressEvent, Stage, TrackProgress};
+pub use format::OutputFormat;
+pub use loudness::{LoudnessMeasurement, LoudnessTarget};
+pub use options::{ConversionOptions, SampleFormat};
+pub use probe::MediaInfo;
+pub use selection::StreamSelector;
+pub use silence::{SilenceInterval, SilenceOptions};
+pub use tracks::TrackOutput;
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+
+use crate::ConversionError;
+
+/// Loudness to normalize to with ffmpeg's two-pass `loudnorm` (EBU R128).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoudnessTarget {
+    /// Integrated loudness, in LUFS.
+    pub integrated_lufs: f64,
+    /// Maximum true peak, in dBTP.
+    pub true_peak_db: f64,
+    /// Loudness range, in LU.
+    pub loudness_range_lu: f64,
+}
+
+impl LoudnessTarget {
+    /// Target integrated loudness with a -1.5 dBTP ceiling and 11 LU range.
+    pub fn new(integrated_lufs: f64) -> Self {
+        Self {
+            integrated_lufs,
+            true_peak_db: -1.5,
+            loudness_range_lu: 11.0,
+        }
+    }
+
+    /// Sets the true peak ceiling, in dBTP.
+    pub fn true_peak(mut self, db: f64) -> Self {
+        self.true_peak_db = db;
+        self
+    }
+
+    /// Sets the loudness range, in LU.
+    pub fn loudness_range(mut self, lu: f64) -> Self {
+        self.loudness_range_lu = lu;
+        self
+    }
+
+    /// Checks the values against the ranges ffmpeg's loudnorm filter
+    /// accepts.
+    pub fn validate(&self) -> Result<(), ConversionError> {
+        if !(-70.0..=-5.0).contains(&self.integrated_lufs) {
+            return Err(ConversionError::InvalidOptions(format!(
+                "integrated loudness must be between -70 and -5 LUFS, got {}",
+                self.integrated_lufs
+            )));
+        }
+        if !(-9.0..=0.0).contains(&self.true_peak_db) {
+            return Err(ConversionError::InvalidOptions(format!(
+                "true peak must be between -9 and 0 dBTP, got {}",
+                self.true_peak_db
+            )));
+        }
+        if !(1.0..=50.0).contains(&self.loudness_range_lu) {
+            return Err(ConversionError::InvalidOptions(format!(
+                "loudness range must be between 1 and 50 LU, got {}",
+                self.loudness_range_lu
+            )));
+        }
+        Ok(())
+    }
+
+    fn base_filter(&self) -> String {
+        format!("loudnorm=I={}:TP={}:LRA={}", self.integrated_lufs, self.true_peak_db, self.loudness_range_lu)
+    }
+
+    /// First pass: measure only, printing the results as JSON on stderr.
+    pub fn analysis_filter(&self) -> String {
+        format!("{}:print_format=json", self.base_filter())
+    }
+
+    /// Second pass: apply a single linear gain computed from the first pass.
+    pub fn normalization_filter(&self, measured: &LoudnessMeasurement) -> String {
+        format!(
+            "{}:measured_I={}:measured_TP={}:measured_LRA={}:measured_thresh={}:offset={}:linear=true",
+            self.base_filter(),
+            measured.input_i,
+            measured.input_tp,
+            measured.input_lra,
+            measured.input_thresh,
+            measured.target_offset
+        )
+    }
+}
+
+/// What loudnorm's first pass measured, as fed back into the second.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoudnessMeasurement {
+    /// Integrated loudness of the input, in LUFS.
+    pub input_i: f64,
+    /// True peak of the input, in dBTP.
+    pub input_tp: f64,
+    /// Loudness range of the input, in LU.
+    pub input_lra: f64,
+    /// Gating threshold, in LUFS.
+    pub input_thresh: f64,
+    /// Gain offset for the second pass, in LU.
+    pub target_offset: f64,
+}
+
+impl LoudnessMeasurement {
+    /// Extracts the JSON block loudnorm prints after its
+    /// `[Parsed_loudnorm_N @ ...]` line. Silent audio has no loudness and
+    /// fails with `InvalidFormat`.
+    pub fn from_stderr(stderr: &str) -> Result<Self, ConversionError> {
+        let missing = || ConversionError::FFmpegError("loudnorm did not report a measurement".to_string());
+
+        let marker = stderr.rfind("Parsed_loudnorm").ok_or_else(missing)?;
+        let rest = &stderr[marker..];
+        let start = rest.find('{').ok_or_else(missing)?;
+        let end = rest[start..].find('}').ok_or_else(missing)? + start;
+
+        let values: HashMap<String, String> =
+            serde_json::from_str(&rest[start..=end]).map_err(|e| ConversionError::FFmpegError(e.to_string()))?;
+        let field = |name: &str| -> Result<f64, ConversionError> {
+            values.get(name).and_then(|v| v.trim().parse().ok()).ok_or_else(missing)
+        };
+
+        let measurement = Self {
+            input_i: field("input_i")?,
+            input_tp: field("input_tp")?,
+            input_lra: field("input_lra")?,
+            input_thresh: field("input_thresh")?,
+            target_offset: field("target_offset")?,
+        };
+        if !measurement.input_i.is_finite() || !measurement.input_thresh.is_finite() {
+            return Err(ConversionError::InvalidFormat("audio is silent and cannot be normalized".to_string()));
+        }
+        Ok(measurement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STDERR: &str = r#"size=N/A time=00:03:00.00 bitrate=N/A speed= 250x
+[Parsed_loudnorm_0 @ 0x5631c0e0c2c0]
+{
+	"input_i" : "-27.61",
+	"input_tp" : "-4.47",
+	"input_lra" : "18.06",
+	"input_thresh" : "-39.20",
+	"output_i" : "-16.58",
+	"output_tp" : "-1.50",
+	"output_lra" : "14.78",
+	"output_thresh" : "-27.71",
+	"normalization_type" : "dynamic",
+	"target_offset" : "0.58"
+}
+"#;
+
+    #[test]
+    fn test_parse_measurement() {
+        let measured = LoudnessMeasurement::from_stderr(STDERR).unwrap();
+        assert_eq!(measured.input_i, -27.61);
+        assert_eq!(measured.input_tp, -4.47);
+        assert_eq!(measured.target_offset, 0.58);
+
+        let filter = LoudnessTarget::new(-16.0).normalization_filter(&measured);
+        assert_eq!(
+            filter,
+            "loudnorm=I=-16:TP=-1.5:LRA=11:measured_I=-27.61:measured_TP=-4.47:measured_LRA=18.06:measured_thresh=-39.2:offset=0.58:linear=true"
+        );
+    }
+
+    #[test]
+    fn test_silent_or_missing_measurement() {
+        let silent = STDERR.replace("\"-27.61\"", "\"-inf\"").replace("\"-39.20\"", "\"-inf\"");
+        assert!(matches!(LoudnessMeasurement::from_stderr(&silent), Err(ConversionError::InvalidFormat(_))));
+        assert!(matches!(LoudnessMeasurement::from_stderr("no measurement here"), Err(ConversionError::FFmpegError(_))));
+    }
+
+    #[test]
+    fn test_validate_target() {
+        assert!(LoudnessTarget::new(-16.0).validate().is_ok());
+        assert!(LoudnessTarget::new(-2.0).validate().is_err());
+        assert!(LoudnessTarget::new(-16.0).true_peak(1.0).validate().is_err());
+        assert!(LoudnessTarget::new(-23.0).loudness_range(60.0).validate().is_err());
+    }
+}
@@ -1,260 +1,533 @@
-use std::process::{Command, Stdio};
-use std::sync::{Arc, Mutex};
+use std::error::Error;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::mpsc;
 use std::thread;
 use std::time::Duration;
-use std::io::{BufRead, BufReader};
-use std::path::Path;
-use regex::Regex;
-
-#[derive(Debug)]
-pub struct ConversionProgress {
-    pub duration_seconds: f64,
-    pub processed_seconds: f64,
-    pub percentage: f64,
-    pub speed: f64,
-    pub bitrate: String,
+
+use video_audio_converter::batch::{self, BatchOptions, JobOutcome};
+use video_audio_converter::events::{self, ProgressEvent};
+use video_audio_converter::options::format_timestamp;
+use video_audio_converter::probe::{MediaInfo, StreamInfo};
+use video_audio_converter::{CancellationToken, ConversionError, ConversionOptions, OutputFormat, VideoToAudioConverter};
+
+mod cli;
+mod json_progress;
+
+use cli::{CliArgs, Command, Parsed, ProgressMode, SplitMode, Verbosity};
+use json_progress::JsonReporter;
+
+// Where and how much human-readable output goes
+#[derive(Debug, Clone, Copy)]
+struct Console {
+    to_stderr: bool,
+    verbosity: Verbosity,
+}
+
+macro_rules! say {
+    ($console:expr, $level:expr, $($arg:tt)*) => {
+        if $console.verbosity >= $level {
+            if $console.to_stderr {
+                eprintln!($($arg)*)
+            } else {
+                println!($($arg)*)
+            }
+        }
+    };
+}
+
+// Progress and status lines, hidden by --quiet
+macro_rules! status {
+    ($console:expr, $($arg:tt)*) => { say!($console, Verbosity::Normal, $($arg)*) };
+}
+
+// Extra detail shown with --verbose
+macro_rules! detail {
+    ($console:expr, $($arg:tt)*) => { say!($console, Verbosity::Verbose, $($arg)*) };
+}
+
+// Lines of FFmpeg's output shown with an error unless --verbose asks for all of them
+const ERROR_TAIL_LINES: usize = 5;
+
+fn format_eta(remaining_seconds: f64) -> String {
+    if remaining_seconds < 60.0 {
+        format!("{:.0}s", remaining_seconds)
+    } else {
+        format!("{:.0}m {:.0}s", (remaining_seconds / 60.0).floor(), remaining_seconds % 60.0)
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        0..1_024 => format!("{} B", bytes),
+        1_024..1_048_576 => format!("{:.1} KiB", bytes as f64 / 1_024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+fn progress_printer(console: Console) -> impl FnMut(&ProgressEvent) + Send + 'static {
+    let mut out: Box<dyn Write + Send> = if console.to_stderr { Box::new(std::io::stderr()) } else { Box::new(std::io::stdout()) };
+    let mut last_update = std::time::Instant::now();
+    // Whether the cursor is still at the end of the progress bar line
+    let mut on_bar = false;
+    let mut spinner = 0;
+    move |event| {
+        let progress = match event {
+            ProgressEvent::Progress(progress) => progress,
+            ProgressEvent::StageChanged(stage) => {
+                if std::mem::take(&mut on_bar) {
+                    let _ = writeln!(out);
+                }
+                let _ = writeln!(out, "▶ {}", stage);
+                return;
+            }
+            ProgressEvent::Warning(message) => {
+                if std::mem::take(&mut on_bar) {
+                    let _ = writeln!(out);
+                }
+                let _ = writeln!(out, "⚠️  {}", message);
+                return;
+            }
+            _ => return,
+        };
+
+        let now = std::time::Instant::now();
+        if now.duration_since(last_update) >= Duration::from_millis(250) {
+            let (Some(percentage), Some(duration)) = (progress.percentage, progress.duration_seconds) else {
+                // Unknown length: a spinner with what has been done so far
+                spinner = (spinner + 1) % SPINNER.len();
+                let _ = write!(out, "\r🎵 {} {:.1}s processed | {} written | Speed: {:.2}x   ",
+                    SPINNER[spinner],
+                    progress.processed_seconds,
+                    format_bytes(progress.bytes_written),
+                    progress.speed
+                );
+                let _ = out.flush();
+                last_update = now;
+                on_bar = true;
+                return;
+            };
+
+            // Create progress bar
+            let progress_width = 50;
+            let filled = (percentage.min(100.0) / 100.0 * progress_width as f64) as usize;
+            let empty = progress_width - filled;
+            let bar = format!("{}{}",
+                "█".repeat(filled),
+                "░".repeat(empty)
+            );
+
+            // Calculate ETA
+            let eta = progress.eta_seconds().map_or("calculating...".to_string(), format_eta);
+
+            // Per-track completion when extracting several tracks
+            let tracks: String = progress.tracks.iter()
+                .filter_map(|t| Some(format!("#{} {:.0}% ", t.stream_index, t.percentage?)))
+                .collect();
+
+            let _ = write!(out, "\r🎵 [{}] {:.1}% | Speed: {:.2}x | ETA: {} | {:.1}s/{:.1}s {}",
+                bar,
+                percentage.min(100.0),
+                progress.speed,
+                eta,
+                progress.processed_seconds,
+                duration,
+                tracks
+            );
+            let _ = out.flush();
+            last_update = now;
+            on_bar = true;
+        }
+    }
 }
 
-#[derive(Debug)]
-pub enum ConversionError {
-    FileNotFound,
-    InvalidFormat,
-    FFmpegError(String),
-    IOError(String),
+// Prints progress events (as a bar, or as JSON lines with a reporter) on their
+// own thread so a slow terminal never holds up the thread reading ffmpeg's output
+fn with_progress<T>(reporter: Option<&JsonReporter>, console: Console, operation: impl FnOnce(mpsc::Sender<ProgressEvent>) -> T) -> T {
+    let (sender, receiver) = mpsc::channel();
+    let mut print: Box<dyn FnMut(&ProgressEvent) + Send> = match reporter {
+        Some(reporter) => Box::new(reporter.listener()),
+        None if console.verbosity == Verbosity::Quiet => Box::new(|_: &ProgressEvent| {}),
+        None => Box::new(progress_printer(console)),
+    };
+    let printer = thread::spawn(move || {
+        for event in receiver {
+            print(&event);
+        }
+    });
+    let result = operation(sender);
+    printer.join().unwrap();
+    result
 }
 
-impl std::fmt::Display for ConversionError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match self {
-            ConversionError::FileNotFound => write!(f, "Input file not found"),
-            ConversionError::InvalidFormat => write!(f, "Invalid video format"),
-            ConversionError::FFmpegError(msg) => write!(f, "FFmpeg error: {}", msg),
-            ConversionError::IOError(msg) => write!(f, "IO error: {}", msg),
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    let cli = match cli::parse_args(&args[1..]) {
+        Ok(Parsed::Run(cli)) => cli,
+        Ok(Parsed::Help(command)) => {
+            print!("{}", cli::help(&args[0], command));
+            return;
+        }
+        Ok(Parsed::Version) => {
+            println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        Err(e) => {
+            // Always stderr, so usage errors never end up in JSON read from stdout
+            eprintln!("Error: {}", e);
+            eprintln!("Run '{} --help' for usage.", args[0]);
+            std::process::exit(cli::EXIT_USAGE);
+        }
+    };
+
+    let console = Console { to_stderr: cli.status_to_stderr(), verbosity: cli.verbosity };
+    let code = match run(&cli, console) {
+        Ok(code) => code,
+        Err(e) => report_error(e.as_ref(), console),
+    };
+    std::process::exit(code);
+}
+
+fn report_error(error: &(dyn Error + 'static), console: Console) -> i32 {
+    let conversion_error = error.downcast_ref::<ConversionError>();
+    if let Some(ConversionError::Cancelled) = conversion_error {
+        eprintln!("\n⏹️  Conversion cancelled, partial output removed");
+    } else {
+        eprintln!("\nError: {}", error);
+        if let Some(failure) = conversion_error.and_then(ConversionError::ffmpeg_failure) {
+            let skip = match console.verbosity {
+                Verbosity::Verbose => 0,
+                _ => failure.stderr_tail.len().saturating_sub(ERROR_TAIL_LINES),
+            };
+            eprintln!("Last FFmpeg output:");
+            for line in &failure.stderr_tail[skip..] {
+                eprintln!("  {}", line);
+            }
         }
     }
+    conversion_error.map_or(cli::EXIT_FAILURE, cli::exit_code)
 }
 
-impl std::error::Error for ConversionError {}
+// Returns the exit status for a run that got to the end
+fn run(cli: &CliArgs, console: Console) -> Result<i32, Box<dyn Error>> {
+    let reporter = match (cli.progress, cli.progress_fd) {
+        (ProgressMode::Json, Some(fd)) => Some(JsonReporter::to_fd(fd)?),
+        (ProgressMode::Json, None) => Some(JsonReporter::stdout()),
+        (ProgressMode::Bar, _) => None,
+    };
 
-pub struct VideoToAudioConverter {
-    ffmpeg_path: String,
-    progress: Arc<Mutex<ConversionProgress>>,
+    let result = run_command(cli, console, reporter.as_ref());
+    // Errors outside a conversion (no ffmpeg, unreadable input...) still end the JSON stream with a result
+    if let (Some(reporter), Err(e)) = (&reporter, &result) {
+        reporter.result(&json_progress::error_json(e.as_ref()));
+    }
+    result
 }
 
-impl VideoToAudioConverter {
-    pub fn new() -> Result<Self, ConversionError> {
-        let ffmpeg_path = Self::find_ffmpeg()?;
-        Ok(Self {
-            ffmpeg_path,
-            progress: Arc::new(Mutex::new(ConversionProgress {
-                duration_seconds: 0.0,
-                processed_seconds: 0.0,
-                percentage: 0.0,
-                speed: 0.0,
-                bitrate: String::new(),
-            })),
-        })
+fn run_command(cli: &CliArgs, console: Console, reporter: Option<&JsonReporter>) -> Result<i32, Box<dyn Error>> {
+    let converter = VideoToAudioConverter::with_locator(&cli.ffmpeg)?;
+    detail!(console, "Using FFmpeg {} at {} and {}",
+        converter.ffmpeg_version().map_or("(development build)".to_string(), |v| v.to_string()),
+        converter.ffmpeg_path().unwrap_or("-"),
+        converter.ffprobe_path().unwrap_or("-")
+    );
+    if console.verbosity == Verbosity::Verbose {
+        let encoders: Vec<String> = OutputFormat::ALL
+            .iter()
+            .map(|&format| format!("{} ({})", format, converter.capabilities().encoder_for(format).unwrap_or("unavailable")))
+            .collect();
+        detail!(console, "Encoders: {}", encoders.join(", "));
+    }
+    if cli.command == Command::Probe {
+        return run_probe(&converter, cli, console);
     }
 
-    fn find_ffmpeg() -> Result<String, ConversionError> {
-        let paths = vec!["ffmpeg", "/usr/bin/ffmpeg", "/usr/local/bin/ffmpeg"];
-        
-        for path in paths {
-            if Command::new(path).arg("-version").output().is_ok() {
-                return Ok(path.to_string());
+    // Ctrl-C stops ffmpeg and removes the half-written output
+    let cancel = CancellationToken::new();
+    ctrlc::set_handler({
+        let cancel = cancel.clone();
+        move || cancel.cancel()
+    })?;
+    let options = &cli.options.clone().cancellation(cancel);
+
+    match cli.command {
+        Command::Batch => run_batch(&converter, &cli.positional, options, &cli.batch, reporter, console),
+        Command::Analyze => run_analyze(&converter, cli, options, console),
+        _ => run_conversion(&converter, cli, options, console, reporter),
+    }
+}
+
+fn run_conversion(converter: &VideoToAudioConverter, cli: &CliArgs, options: &ConversionOptions, console: Console, reporter: Option<&JsonReporter>) -> Result<i32, Box<dyn Error>> {
+    let input_path = &cli.positional[0];
+    let output_path = &cli.positional[1];
+    let (from_stdin, to_stdout) = (input_path == "-", output_path == "-");
+    if (from_stdin || to_stdout) && (cli.all_tracks || cli.command == Command::Split) {
+        return Err(ConversionError::InvalidOptions("stdin and stdout only work for converting to a single file".to_string()).into());
+    }
+    let format = match options.format_override() {
+        Some(format) => format,
+        None if to_stdout => return Err(ConversionError::InvalidOptions("writing to stdout needs --format".to_string()).into()),
+        None => options.resolve_format(output_path)?,
+    };
+
+    status!(console, "Starting conversion: {} -> {} ({})",
+        if from_stdin { "stdin" } else { input_path },
+        if to_stdout { "stdout" } else { output_path },
+        format
+    );
+
+    // The input is described from the converter's own probe, reported with Started
+    let listener = |sender| {
+        let mut forward = events::forward_to(sender);
+        let options = options.clone();
+        move |event: &ProgressEvent| {
+            if let ProgressEvent::Started { media_info, .. } = event && !from_stdin {
+                print_input_summary(media_info, &options, console);
             }
+            forward(event);
+        }
+    };
+
+    let start_time = std::time::Instant::now();
+
+    if cli.all_tracks {
+        let tracks = with_progress(reporter, console, |sender| {
+            converter.extract_tracks(input_path, output_path, &cli.selectors, options, listener(sender))
+        })?;
+
+        let elapsed = start_time.elapsed();
+        status!(console, "\n✅ Extracted {} track(s) in {:.2}s", tracks.len(), elapsed.as_secs_f64());
+        for track in &tracks {
+            status!(console, "Output file: {} (stream #{}, {})",
+                track.output_path,
+                track.stream.index,
+                track.stream.language.as_deref().unwrap_or("und")
+            );
         }
-        
-        Err(ConversionError::FFmpegError("FFmpeg not found in PATH".to_string()))
+        return Ok(0);
     }
 
-    pub fn get_video_duration(&self, input_path: &str) -> Result<f64, ConversionError> {
-        let output = Command::new(&self.ffmpeg_path)
-            .args(&["-i", input_path, "-f", "null", "-"])
-            .stderr(Stdio::piped())
-            .stdout(Stdio::null())
-            .spawn()
-            .map_err(|e| ConversionError::IOError(e.to_string()))?
-            .wait_with_output()
-            .map_err(|e| ConversionError::IOError(e.to_string()))?;
-
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        let duration_regex = Regex::new(r"Duration: (\d{2}):(\d{2}):(\d{2})\.(\d{2})").unwrap();
-        
-        if let Some(captures) = duration_regex.captures(&stderr) {
-            let hours: f64 = captures[1].parse().unwrap_or(0.0);
-            let minutes: f64 = captures[2].parse().unwrap_or(0.0);
-            let seconds: f64 = captures[3].parse().unwrap_or(0.0);
-            let centiseconds: f64 = captures[4].parse().unwrap_or(0.0);
-            
-            Ok(hours * 3600.0 + minutes * 60.0 + seconds + centiseconds / 100.0)
-        } else {
-            Err(ConversionError::InvalidFormat)
+    if cli.split == Some(SplitMode::Chapters) {
+        let outputs = with_progress(reporter, console, |sender| {
+            converter.split_by_chapters(input_path, output_path, options, listener(sender))
+        })?;
+
+        let elapsed = start_time.elapsed();
+        status!(console, "\n✅ Split {} chapter(s) in {:.2}s", outputs.len(), elapsed.as_secs_f64());
+        for output in &outputs {
+            status!(console, "Output file: {} ({} - {})",
+                output.output_path,
+                format_timestamp(output.chapter.start_seconds),
+                format_timestamp(output.chapter.end_seconds)
+            );
         }
+        return Ok(0);
     }
 
-    pub fn convert<F>(&self, input_path: &str, output_path: &str, mut progress_callback: F) -> Result<(), ConversionError>
-    where
-        F: FnMut(&ConversionProgress) + Send + 'static,
-    {
-        if !Path::new(input_path).exists() {
-            return Err(ConversionError::FileNotFound);
+    if cli.split == Some(SplitMode::Silence) {
+        let (silences, outputs) = with_progress(reporter, console, |sender| {
+            converter.split_by_silence(input_path, output_path, options, &cli.silence, listener(sender))
+        })?;
+
+        let elapsed = start_time.elapsed();
+        status!(console, "\n✅ Found {} silent gap(s), wrote {} segment(s) in {:.2}s", silences.len(), outputs.len(), elapsed.as_secs_f64());
+        for output in &outputs {
+            status!(console, "Output file: {} ({} - {})",
+                output.output_path,
+                format_timestamp(output.chapter.start_seconds),
+                format_timestamp(output.chapter.end_seconds)
+            );
         }
+        return Ok(0);
+    }
 
-        let duration = self.get_video_duration(input_path)?;
-        {
-            let mut progress = self.progress.lock().unwrap();
-            progress.duration_seconds = duration;
+    with_progress(reporter, console, |sender| {
+        let listener = listener(sender);
+        match (from_stdin, to_stdout) {
+            (false, false) => converter.convert(input_path, output_path, options, listener),
+            (true, false) => converter.convert_reader(std::io::stdin(), output_path, options, listener),
+            (false, true) => converter.convert_to_writer(input_path, std::io::stdout(), options, listener),
+            (true, true) => converter.convert_stream(std::io::stdin(), std::io::stdout(), options, listener),
         }
+    })?;
 
-        let mut child = Command::new(&self.ffmpeg_path)
-            .args(&[
-                "-i", input_path,
-                "-vn",                    // No video
-                "-acodec", "libmp3lame",  // MP3 codec
-                "-ab", "192k",            // Audio bitrate
-                "-ar", "44100",           // Sample rate
-                "-y",                     // Overwrite output file
-                "-progress", "pipe:2",    // Progress to stderr
-                output_path
-            ])
-            .stdout(Stdio::null())
-            .stderr(Stdio::piped())
-            .stdin(Stdio::null())
-            .spawn()
-            .map_err(|e| ConversionError::IOError(e.to_string()))?;
-
-        let stderr = child.stderr.take().unwrap();
-        let reader = BufReader::new(stderr);
-        let progress_arc = Arc::clone(&self.progress);
-
-        let progress_thread = thread::spawn(move || {
-            let time_regex = Regex::new(r"out_time_ms=(\d+)").unwrap();
-            let speed_regex = Regex::new(r"speed=([0-9.]+)x").unwrap();
-            let bitrate_regex = Regex::new(r"bitrate=([0-9.]+kbits/s)").unwrap();
-
-            for line in reader.lines() {
-                if let Ok(line) = line {
-                    let mut progress = progress_arc.lock().unwrap();
-                    
-                    if let Some(captures) = time_regex.captures(&line) {
-                        if let Ok(microseconds) = captures[1].parse::<u64>() {
-                            progress.processed_seconds = microseconds as f64 / 1_000_000.0;
-                            if progress.duration_seconds > 0.0 {
-                                progress.percentage = (progress.processed_seconds / progress.duration_seconds) * 100.0;
-                            }
-                        }
-                    }
-                    
-                    if let Some(captures) = speed_regex.captures(&line) {
-                        if let Ok(speed) = captures[1].parse::<f64>() {
-                            progress.speed = speed;
-                        }
-                    }
-                    
-                    if let Some(captures) = bitrate_regex.captures(&line) {
-                        progress.bitrate = captures[1].to_string();
-                    }
-
-                    progress_callback(&progress);
-                }
-            }
-        });
+    let elapsed = start_time.elapsed();
+    status!(console, "\n✅ Conversion completed in {:.2}s", elapsed.as_secs_f64());
+    status!(console, "Output file: {}", if to_stdout { "stdout" } else { output_path });
+
+    Ok(0)
+}
 
-        let status = child.wait().map_err(|e| ConversionError::IOError(e.to_string()))?;
-        progress_thread.join().unwrap();
+fn print_input_summary(media_info: &MediaInfo, options: &ConversionOptions, console: Console) {
+    status!(console, "Input: {} | {} | {} audio stream(s)",
+        media_info.container,
+        media_info.duration_seconds.map_or("unknown duration".to_string(), |d| format!("{:.1}s", d)),
+        media_info.audio_streams().count()
+    );
+    for stream in &media_info.streams {
+        detail!(console, "  {}", describe_stream(stream));
+    }
+    if options.is_trimmed() && let Ok(Some(length)) = options.output_duration(media_info.duration_seconds) {
+        status!(console, "Segment: {} long", format_timestamp(length));
+    }
+}
 
-        if !status.success() {
-            return Err(ConversionError::FFmpegError("Conversion failed".to_string()));
+fn run_batch(
+    converter: &VideoToAudioConverter,
+    positional: &[String],
+    options: &ConversionOptions,
+    batch: &BatchOptions,
+    reporter: Option<&JsonReporter>,
+    console: Console,
+) -> Result<i32, Box<dyn Error>> {
+    let (sources, output_root) = positional.split_at(positional.len() - 1);
+    let output_root = Path::new(&output_root[0]);
+    let extension = options
+        .format_override()
+        .map_or("mp3", |format| format.extensions()[0]);
+
+    let jobs = batch::collect_jobs(sources, output_root, extension, batch)?;
+    let total = jobs.len();
+    status!(console, "Batch: {} file(s) -> {}", total, output_root.display());
+
+    let finished = Mutex::new(0);
+    if let Some(ledger) = batch.ledger_path() {
+        detail!(console, "Ledger: {}", ledger.display());
+    }
+    let summary = batch::run_batch(converter, jobs, options, batch, |result| {
+        if let Some(reporter) = reporter {
+            reporter.write(&json_progress::job_json(result));
         }
+        let mut finished = finished.lock().unwrap();
+        *finished += 1;
+        let status = match &result.outcome {
+            JobOutcome::Converted => format!("✅ {:.1}s", result.elapsed.as_secs_f64()),
+            JobOutcome::Skipped(reason) => format!("⏭️  skipped: {}", reason),
+            JobOutcome::Failed(e) => format!("❌ {}", e),
+        };
+        status!(console, "[{}/{}] {} {}", finished, total, result.job.input.display(), status);
+    })?;
 
-        Ok(())
+    if let Some(reporter) = reporter {
+        reporter.result(&json_progress::batch_json(&summary));
+    }
+    status!(console, "\nConverted: {} | Skipped: {} | Failed: {} | Time: {:.2}s",
+        summary.converted(),
+        summary.skipped(),
+        summary.failed(),
+        summary.elapsed.as_secs_f64()
+    );
+    for result in &summary.results {
+        if let JobOutcome::Failed(e) = &result.outcome {
+            eprintln!("  ❌ {}: {}", result.job.input.display(), e);
+        }
     }
+
+    Ok(if summary.failed() > 0 { cli::EXIT_BATCH_FAILURES } else { 0 })
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args: Vec<String> = std::env::args().collect();
-    
-    if args.len() != 3 {
-        println!("Usage: {} <input_video> <output_audio.mp3>", args[0]);
-        std::process::exit(1);
+// The probe result is the output, so it is printed even with --quiet
+fn run_probe(converter: &VideoToAudioConverter, cli: &CliArgs, console: Console) -> Result<i32, Box<dyn Error>> {
+    let media_info = converter.probe(&cli.positional[0])?;
+    if cli.json {
+        println!("{}", json_progress::media_json(&media_info));
+        return Ok(0);
     }
 
-    let input_path = &args[1];
-    let output_path = &args[2];
+    print_media_info(&media_info, console.verbosity == Verbosity::Verbose);
+    Ok(0)
+}
 
-    println!("Starting conversion: {} -> {}", input_path, output_path);
-    
-    let converter = VideoToAudioConverter::new()?;
-    
-    let start_time = std::time::Instant::now();
-    
-    converter.convert(input_path, output_path, {
-        let mut last_update = std::time::Instant::now();
-        move |progress| {
-            let now = std::time::Instant::now();
-            if now.duration_since(last_update) >= Duration::from_millis(250) {
-                // Create progress bar
-                let progress_width = 50;
-                let filled = (progress.percentage / 100.0 * progress_width as f64) as usize;
-                let empty = progress_width - filled;
-                let bar = format!("{}{}",
-                    "█".repeat(filled),
-                    "░".repeat(empty)
-                );
-                
-                // Calculate ETA
-                let eta = if progress.speed > 0.0 {
-                    let remaining_seconds = (progress.duration_seconds - progress.processed_seconds) / progress.speed;
-                    if remaining_seconds < 60.0 {
-                        format!("{:.0}s", remaining_seconds)
-                    } else {
-                        format!("{:.0}m {:.0}s", remaining_seconds / 60.0, remaining_seconds % 60.0)
-                    }
-                } else {
-                    "calculating...".to_string()
-                };
-
-                print!("\r🎵 [{}] {:.1}% | Speed: {:.2}x | ETA: {} | {:.1}s/{:.1}s ",
-                    bar,
-                    progress.percentage.min(100.0),
-                    progress.speed,
-                    eta,
-                    progress.processed_seconds,
-                    progress.duration_seconds
-                );
-                std::io::Write::flush(&mut std::io::stdout()).unwrap();
-                last_update = now;
+fn print_media_info(info: &MediaInfo, verbose: bool) {
+    println!("Container: {}", info.container);
+    println!("Duration:  {}", info.duration_seconds.map_or("unknown".to_string(), format_timestamp));
+    if let Some(bitrate) = info.bitrate {
+        println!("Bitrate:   {} kb/s", bitrate / 1000);
+    }
+    println!("Streams:");
+    for stream in &info.streams {
+        println!("  {}", describe_stream(stream));
+        if verbose {
+            for (key, value) in &stream.tags {
+                println!("      {}: {}", key, value);
             }
         }
-    })?;
-
-    let elapsed = start_time.elapsed();
-    println!("\n✅ Conversion completed in {:.2}s", elapsed.as_secs_f64());
-    println!("Output file: {}", output_path);
+    }
+    if !info.chapters.is_empty() {
+        println!("Chapters:");
+        for (number, chapter) in info.chapters.iter().enumerate() {
+            println!("  {:>2}  {} - {}  {}",
+                number + 1,
+                format_timestamp(chapter.start_seconds),
+                format_timestamp(chapter.end_seconds),
+                chapter.title.as_deref().unwrap_or("")
+            );
+        }
+    }
+    if verbose {
+        for (key, value) in &info.tags {
+            println!("{}: {}", key, value);
+        }
+    }
+}
 
-    Ok(())
+fn describe_stream(stream: &StreamInfo) -> String {
+    let mut text = format!("#{} {} {}", stream.index, json_progress::kind_name(stream.kind), stream.codec.as_deref().unwrap_or("?"));
+    if let Some(language) = &stream.language {
+        text.push_str(&format!(" {}", language));
+    }
+    if let Some(channels) = stream.channels {
+        text.push_str(&format!(", {} channel(s)", channels));
+    }
+    if let Some(rate) = stream.sample_rate {
+        text.push_str(&format!(", {} Hz", rate));
+    }
+    if let Some(title) = &stream.title {
+        text.push_str(&format!(" \"{}\"", title));
+    }
+    if stream.disposition.default {
+        text.push_str(" (default)");
+    }
+    text
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_converter_creation() {
-        let result = VideoToAudioConverter::new();
-        // This test will pass if FFmpeg is installed
-        match result {
-            Ok(_) => println!("FFmpeg found"),
-            Err(e) => println!("FFmpeg not available: {}", e),
+fn run_analyze(converter: &VideoToAudioConverter, cli: &CliArgs, options: &ConversionOptions, console: Console) -> Result<i32, Box<dyn Error>> {
+    let input_path = &cli.positional[0];
+
+    let measured = with_progress(None, console, |sender| {
+        converter.measure_loudness(input_path, options, events::forward_to(sender))
+    })?;
+    let silences = match cli.find_silence {
+        true => {
+            status!(console, "\n▶ Detecting silence");
+            Some(converter.detect_silence(input_path, options, &cli.silence)?)
         }
+        false => None,
+    };
+
+    if cli.json {
+        println!("{}", json_progress::analysis_json(&measured, silences.as_deref()));
+        return Ok(0);
     }
 
-    #[test]
-    fn test_invalid_file() {
-        if let Ok(converter) = VideoToAudioConverter::new() {
-            let result = converter.convert("nonexistent.mp4", "output.mp3", |_| {});
-            assert!(matches!(result, Err(ConversionError::FileNotFound)));
+    status!(console, "");
+    println!("Integrated loudness: {:.1} LUFS", measured.input_i);
+    println!("True peak:           {:.1} dBTP", measured.input_tp);
+    println!("Loudness range:      {:.1} LU", measured.input_lra);
+    println!("Threshold:           {:.1} LUFS", measured.input_thresh);
+    if let Some(silences) = silences {
+        println!("Silent gaps:         {}", silences.len());
+        for silence in &silences {
+            println!("  {} - {} ({:.1}s)",
+                format_timestamp(silence.start_seconds),
+                format_timestamp(silence.end_seconds),
+                silence.duration_seconds()
+            );
         }
     }
+    Ok(0)
 }
@@ -0,0 +1,660 @@
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::ConversionError;
+use crate::cancel::CancellationToken;
+use crate::capabilities::{Capabilities, EXPERIMENTAL_ENCODERS};
+use crate::format::OutputFormat;
+use crate::loudness::LoudnessTarget;
+use crate::selection::StreamSelector;
+
+const DEFAULT_BITRATE_KBPS: u32 = 192;
+const DEFAULT_SAMPLE_RATE: u32 = 44100;
+
+/// Bit depth of lossless output. Lossy encoders pick their own internal
+/// format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleFormat {
+    /// Unsigned 8-bit (WAV only).
+    U8,
+    /// Signed 16-bit.
+    S16,
+    /// Signed 24-bit.
+    S24,
+    /// Signed 32-bit (WAV only).
+    S32,
+    /// 32-bit float (WAV only).
+    F32,
+}
+
+impl SampleFormat {
+    /// Short name, as accepted by `FromStr` and shown by `Display`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SampleFormat::U8 => "u8",
+            SampleFormat::S16 => "s16",
+            SampleFormat::S24 => "s24",
+            SampleFormat::S32 => "s32",
+            SampleFormat::F32 => "f32",
+        }
+    }
+}
+
+impl fmt::Display for SampleFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for SampleFormat {
+    type Err = ConversionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "u8" => Ok(SampleFormat::U8),
+            "s16" | "16" => Ok(SampleFormat::S16),
+            "s24" | "24" => Ok(SampleFormat::S24),
+            "s32" | "32" => Ok(SampleFormat::S32),
+            "f32" | "flt" | "float" => Ok(SampleFormat::F32),
+            _ => Err(ConversionError::InvalidOptions(format!("unknown sample format '{}'", s))),
+        }
+    }
+}
+
+/// How a conversion encodes its output, which part of the input it takes
+/// and how it can be cancelled.
+///
+/// Unset options leave the choice to the output format: lossy formats get
+/// 192 kbit/s and 44.1 kHz (48 kHz for Opus), lossless ones keep the source
+/// sample rate.
+#[derive(Debug, Clone, Default)]
+pub struct ConversionOptions {
+    format: Option<OutputFormat>,
+    bitrate_kbps: Option<u32>,
+    vbr_quality: Option<f32>,
+    sample_rate: Option<u32>,
+    channels: Option<u32>,
+    sample_format: Option<SampleFormat>,
+    compression_level: Option<u32>,
+    audio_stream: Option<StreamSelector>,
+    start_seconds: Option<f64>,
+    end_seconds: Option<f64>,
+    duration_seconds: Option<f64>,
+    loudness: Option<LoudnessTarget>,
+    cancellation: Option<CancellationToken>,
+}
+
+impl ConversionOptions {
+    /// Options with nothing set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Overrides the format inferred from the output extension.
+    pub fn format(mut self, format: OutputFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Constant bitrate in kbit/s.
+    pub fn bitrate(mut self, kbps: u32) -> Self {
+        self.bitrate_kbps = Some(kbps);
+        self
+    }
+
+    /// Encoder-specific VBR scale: 0-9 for MP3 (lower is better),
+    /// -1-10 for Vorbis and 0.1-2 for AAC (higher is better).
+    pub fn vbr_quality(mut self, quality: f32) -> Self {
+        self.vbr_quality = Some(quality);
+        self
+    }
+
+    /// Output sample rate in Hz.
+    pub fn sample_rate(mut self, hz: u32) -> Self {
+        self.sample_rate = Some(hz);
+        self
+    }
+
+    /// Number of output channels, downmixing or upmixing the source.
+    pub fn channels(mut self, channels: u32) -> Self {
+        self.channels = Some(channels);
+        self
+    }
+
+    /// Bit depth of lossless output.
+    pub fn sample_format(mut self, sample_format: SampleFormat) -> Self {
+        self.sample_format = Some(sample_format);
+        self
+    }
+
+    /// Compression effort: 0-12 for FLAC, 0-2 for ALAC.
+    pub fn compression_level(mut self, level: u32) -> Self {
+        self.compression_level = Some(level);
+        self
+    }
+
+    /// Which audio stream to extract; ffmpeg's own choice when unset.
+    pub fn audio_stream(mut self, selector: StreamSelector) -> Self {
+        self.audio_stream = Some(selector);
+        self
+    }
+
+    /// The selector set with [`audio_stream`](Self::audio_stream).
+    pub fn audio_stream_selector(&self) -> Option<&StreamSelector> {
+        self.audio_stream.as_ref()
+    }
+
+    /// Start of the segment to extract, in seconds from the beginning of the
+    /// input.
+    pub fn start(mut self, seconds: f64) -> Self {
+        self.start_seconds = Some(seconds);
+        self
+    }
+
+    /// End of the segment; mutually exclusive with `duration`.
+    pub fn end(mut self, seconds: f64) -> Self {
+        self.end_seconds = Some(seconds);
+        self
+    }
+
+    /// Length of the segment; mutually exclusive with `end`.
+    pub fn duration(mut self, seconds: f64) -> Self {
+        self.duration_seconds = Some(seconds);
+        self
+    }
+
+    /// Two-pass EBU R128 normalization to the given target.
+    pub fn normalize_loudness(mut self, target: LoudnessTarget) -> Self {
+        self.loudness = Some(target);
+        self
+    }
+
+    /// The target set with [`normalize_loudness`](Self::normalize_loudness).
+    pub fn loudness_target(&self) -> Option<&LoudnessTarget> {
+        self.loudness.as_ref()
+    }
+
+    /// Cancelling the token kills ffmpeg and removes the partial output.
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// The token set with [`cancellation`](Self::cancellation).
+    pub fn cancellation_token(&self) -> Option<&CancellationToken> {
+        self.cancellation.as_ref()
+    }
+
+    /// Whether a sample rate was set.
+    pub fn has_sample_rate(&self) -> bool {
+        self.sample_rate.is_some()
+    }
+
+    /// Whether a start, end or duration was set.
+    pub fn is_trimmed(&self) -> bool {
+        self.start_seconds.is_some() || self.end_seconds.is_some() || self.duration_seconds.is_some()
+    }
+
+    /// Length of the audio that will be written for an input of
+    /// `input_duration` seconds.
+    pub fn trimmed_duration(&self, input_duration: f64) -> Result<f64, ConversionError> {
+        let start = self.start_seconds.unwrap_or(0.0);
+        let end = self.segment_end()?.unwrap_or(input_duration).min(input_duration);
+
+        if start >= input_duration {
+            return Err(ConversionError::InvalidOptions(format!(
+                "start {} is past the end of the input ({})",
+                format_timestamp(start),
+                format_timestamp(input_duration)
+            )));
+        }
+        Ok(end - start)
+    }
+
+    /// Like [`trimmed_duration`](Self::trimmed_duration), for inputs whose
+    /// length may be unknown (live streams, pipes). Only a trim with an end
+    /// point gives a length then.
+    pub fn output_duration(&self, input_duration: Option<f64>) -> Result<Option<f64>, ConversionError> {
+        match input_duration {
+            Some(duration) => self.trimmed_duration(duration).map(Some),
+            None => Ok(self.segment_end()?.map(|end| end - self.start_seconds.unwrap_or(0.0))),
+        }
+    }
+
+    /// Seek arguments split into those placed before `-i` and those after
+    /// it. Input seeking jumps straight to the start point and is
+    /// frame-accurate when transcoding; it is only unsafe for containers
+    /// without a reliable index, where the caller should pass
+    /// `fast_seek = false` to decode from the start.
+    pub fn trim_args(&self, fast_seek: bool) -> Result<(Vec<String>, Vec<String>), ConversionError> {
+        let mut seek = Vec::new();
+        if let Some(start) = self.start_seconds.filter(|s| *s > 0.0) {
+            seek.extend(["-ss".to_string(), start.to_string()]);
+        }
+        if let Some(end) = self.segment_end()? {
+            let length = end - self.start_seconds.unwrap_or(0.0);
+            seek.extend(["-t".to_string(), length.to_string()]);
+        }
+
+        if fast_seek { Ok((seek, Vec::new())) } else { Ok((Vec::new(), seek)) }
+    }
+
+    fn segment_end(&self) -> Result<Option<f64>, ConversionError> {
+        let invalid = |msg: &str| Err(ConversionError::InvalidOptions(msg.to_string()));
+        let start = self.start_seconds.unwrap_or(0.0);
+
+        if start < 0.0 {
+            return invalid("start time cannot be negative");
+        }
+        match (self.end_seconds, self.duration_seconds) {
+            (Some(_), Some(_)) => invalid("end time and duration are mutually exclusive"),
+            (Some(end), None) if end <= start => invalid("end time must be after the start time"),
+            (Some(end), None) => Ok(Some(end)),
+            (None, Some(duration)) if duration <= 0.0 => invalid("duration must be positive"),
+            (None, Some(duration)) => Ok(Some(start + duration)),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// The format set with [`format`](Self::format).
+    pub fn format_override(&self) -> Option<OutputFormat> {
+        self.format
+    }
+
+    /// The format set in the options, or else the one the extension of
+    /// `output_path` implies.
+    pub fn resolve_format(&self, output_path: &str) -> Result<OutputFormat, ConversionError> {
+        match self.format {
+            Some(format) => Ok(format),
+            None => OutputFormat::from_path(output_path),
+        }
+    }
+
+    /// Checks the options against each other and against `format`, failing
+    /// with `InvalidOptions`.
+    pub fn validate(&self, format: OutputFormat) -> Result<(), ConversionError> {
+        let invalid = |msg: String| Err(ConversionError::InvalidOptions(msg));
+
+        if self.bitrate_kbps.is_some() && self.vbr_quality.is_some() {
+            return invalid("bitrate and VBR quality are mutually exclusive".to_string());
+        }
+
+        if let Some(kbps) = self.bitrate_kbps {
+            match bitrate_range(format) {
+                Some((min, max)) if (min..=max).contains(&kbps) => {}
+                Some((min, max)) => {
+                    return invalid(format!("{} bitrate must be between {}k and {}k, got {}k", format, min, max, kbps));
+                }
+                None => return invalid(format!("{} is lossless and does not take a bitrate", format)),
+            }
+        }
+
+        if let Some(quality) = self.vbr_quality {
+            match vbr_range(format) {
+                Some((min, max)) if (min..=max).contains(&quality) => {}
+                Some((min, max)) => {
+                    return invalid(format!("{} VBR quality must be between {} and {}, got {}", format, min, max, quality));
+                }
+                None => return invalid(format!("{} does not support VBR quality levels", format)),
+            }
+        }
+
+        if let Some(hz) = self.sample_rate {
+            let ok = match supported_sample_rates(format) {
+                Some(rates) => rates.contains(&hz),
+                None => (8000..=192000).contains(&hz),
+            };
+            if !ok {
+                return invalid(format!("{} does not support a sample rate of {} Hz", format, hz));
+            }
+        }
+
+        if let Some(channels) = self.channels
+            && (channels == 0 || channels > max_channels(format))
+        {
+            return invalid(format!("{} supports 1 to {} channels, got {}", format, max_channels(format), channels));
+        }
+
+        if let Some(sample_format) = self.sample_format
+            && !supported_sample_formats(format).contains(&sample_format)
+        {
+            return invalid(format!("{} does not support {} samples", format, sample_format));
+        }
+
+        if let Some(level) = self.compression_level {
+            match compression_range(format) {
+                Some(max) if level <= max => {}
+                Some(max) => return invalid(format!("{} compression level must be between 0 and {}, got {}", format, max, level)),
+                None => return invalid(format!("{} does not take a compression level", format)),
+            }
+        }
+
+        if let Some(target) = &self.loudness {
+            target.validate()?;
+        }
+
+        Ok(())
+    }
+
+    /// Codec, quality and container arguments for the given output path,
+    /// using the standard encoder of the format.
+    pub fn encoder_args(&self, format: OutputFormat, output_path: &str) -> Result<Vec<String>, ConversionError> {
+        self.encoder_args_for(format, output_path, None)
+    }
+
+    // With the capabilities of the ffmpeg build that will run, the best encoder it has is used
+    pub(crate) fn encoder_args_for(
+        &self,
+        format: OutputFormat,
+        output_path: &str,
+        capabilities: Option<&Capabilities>,
+    ) -> Result<Vec<String>, ConversionError> {
+        format.check_output_path(output_path)?;
+        let ext = Path::new(output_path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default();
+        self.codec_args(format, ext, capabilities)
+    }
+
+    /// Like [`encoder_args`](Self::encoder_args), for output written to a
+    /// pipe in a container that does not need to seek back when finishing the
+    /// file.
+    pub fn stream_encoder_args(&self, format: OutputFormat) -> Result<Vec<String>, ConversionError> {
+        self.stream_encoder_args_for(format, None)
+    }
+
+    pub(crate) fn stream_encoder_args_for(&self, format: OutputFormat, capabilities: Option<&Capabilities>) -> Result<Vec<String>, ConversionError> {
+        let ext = format.stream_extension().ok_or_else(|| {
+            ConversionError::InvalidFormat(format!("{} audio cannot be streamed, its containers need a seekable output", format))
+        })?;
+        self.codec_args(format, ext, capabilities)
+    }
+
+    fn codec_args(&self, format: OutputFormat, ext: &str, capabilities: Option<&Capabilities>) -> Result<Vec<String>, ConversionError> {
+        self.validate(format)?;
+
+        let encoder = match (format, self.sample_format, capabilities) {
+            (OutputFormat::Wav, Some(SampleFormat::U8), _) => "pcm_u8",
+            (OutputFormat::Wav, Some(SampleFormat::S24), _) => "pcm_s24le",
+            (OutputFormat::Wav, Some(SampleFormat::S32), _) => "pcm_s32le",
+            (OutputFormat::Wav, Some(SampleFormat::F32), _) => "pcm_f32le",
+            (_, _, Some(capabilities)) => {
+                // libfdk_aac has its own VBR scale; quality levels are those of the native encoder
+                let candidates: Vec<&'static str> = format
+                    .encoders()
+                    .iter()
+                    .copied()
+                    .filter(|encoder| self.vbr_quality.is_none() || *encoder != "libfdk_aac")
+                    .collect();
+                capabilities.first_encoder(format, &candidates)?
+            }
+            (_, _, None) => format.encoder(),
+        };
+        let mut args = vec!["-acodec".to_string(), encoder.to_string()];
+        if EXPERIMENTAL_ENCODERS.contains(&encoder) {
+            args.extend(["-strict".to_string(), "experimental".to_string()]);
+        }
+
+        if let Some(quality) = self.vbr_quality {
+            args.extend(["-q:a".to_string(), quality.to_string()]);
+        } else if !format.is_lossless() {
+            let kbps = self.bitrate_kbps.unwrap_or(DEFAULT_BITRATE_KBPS);
+            args.extend(["-ab".to_string(), format!("{}k", kbps)]);
+        }
+
+        // Lossy output is resampled to a common rate unless told otherwise;
+        // lossless output keeps the source rate.
+        let sample_rate = match (self.sample_rate, format) {
+            (Some(hz), _) => Some(hz),
+            (None, OutputFormat::Opus) => Some(48000),
+            (None, f) if !f.is_lossless() => Some(DEFAULT_SAMPLE_RATE),
+            (None, _) => None,
+        };
+        if let Some(hz) = sample_rate {
+            args.extend(["-ar".to_string(), hz.to_string()]);
+        }
+
+        if let Some(channels) = self.channels {
+            args.extend(["-ac".to_string(), channels.to_string()]);
+        }
+
+        match (format, self.sample_format) {
+            (OutputFormat::Flac, Some(SampleFormat::S16)) => args.extend(["-sample_fmt".to_string(), "s16".to_string()]),
+            (OutputFormat::Flac, Some(SampleFormat::S24)) => {
+                args.extend(["-sample_fmt".to_string(), "s32".to_string(), "-bits_per_raw_sample".to_string(), "24".to_string()])
+            }
+            (OutputFormat::Alac, Some(SampleFormat::S16)) => args.extend(["-sample_fmt".to_string(), "s16p".to_string()]),
+            (OutputFormat::Alac, Some(SampleFormat::S24)) => {
+                args.extend(["-sample_fmt".to_string(), "s32p".to_string(), "-bits_per_raw_sample".to_string(), "24".to_string()])
+            }
+            _ => {}
+        }
+
+        if let Some(level) = self.compression_level {
+            args.extend(["-compression_level".to_string(), level.to_string()]);
+        }
+
+        let muxer = format.muxer(ext);
+        if let Some(capabilities) = capabilities {
+            capabilities.check_muxer(format, muxer)?;
+        }
+        args.extend(["-f".to_string(), muxer.to_string()]);
+        Ok(args)
+    }
+}
+
+/// Parses a timestamp into seconds. Accepts "SS", "MM:SS" and "HH:MM:SS",
+/// each with optional fractional seconds.
+pub fn parse_timestamp(value: &str) -> Result<f64, ConversionError> {
+    let invalid = || ConversionError::InvalidOptions(format!("invalid timestamp '{}'", value));
+
+    let mut seconds = 0.0;
+    let parts: Vec<&str> = value.trim().split(':').collect();
+    if parts.len() > 3 {
+        return Err(invalid());
+    }
+    for (i, part) in parts.iter().enumerate() {
+        let last = i == parts.len() - 1;
+        let number: f64 = part.parse().map_err(|_| invalid())?;
+        if number < 0.0 || !number.is_finite() || (!last && number.fract() != 0.0) || (i > 0 && number >= 60.0) {
+            return Err(invalid());
+        }
+        seconds = seconds * 60.0 + number;
+    }
+    Ok(seconds)
+}
+
+/// Formats seconds as `HH:MM:SS.mmm`.
+pub fn format_timestamp(seconds: f64) -> String {
+    let hours = (seconds / 3600.0).floor();
+    let minutes = ((seconds % 3600.0) / 60.0).floor();
+    format!("{:02}:{:02}:{:06.3}", hours, minutes, seconds % 60.0)
+}
+
+fn bitrate_range(format: OutputFormat) -> Option<(u32, u32)> {
+    match format {
+        OutputFormat::Mp3 => Some((8, 320)),
+        OutputFormat::Aac => Some((8, 512)),
+        OutputFormat::Opus => Some((6, 510)),
+        OutputFormat::Vorbis => Some((45, 500)),
+        OutputFormat::Ac3 => Some((32, 640)),
+        OutputFormat::Flac | OutputFormat::Wav | OutputFormat::Alac => None,
+    }
+}
+
+fn vbr_range(format: OutputFormat) -> Option<(f32, f32)> {
+    match format {
+        OutputFormat::Mp3 => Some((0.0, 9.0)),
+        OutputFormat::Vorbis => Some((-1.0, 10.0)),
+        OutputFormat::Aac => Some((0.1, 2.0)),
+        _ => None,
+    }
+}
+
+// None means any common rate between 8 kHz and 192 kHz
+fn supported_sample_rates(format: OutputFormat) -> Option<&'static [u32]> {
+    match format {
+        OutputFormat::Mp3 => Some(&[8000, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000]),
+        OutputFormat::Opus => Some(&[8000, 12000, 16000, 24000, 48000]),
+        OutputFormat::Ac3 => Some(&[32000, 44100, 48000]),
+        _ => None,
+    }
+}
+
+fn max_channels(format: OutputFormat) -> u32 {
+    match format {
+        OutputFormat::Mp3 => 2,
+        OutputFormat::Ac3 => 6,
+        OutputFormat::Wav => 64,
+        _ => 8,
+    }
+}
+
+fn supported_sample_formats(format: OutputFormat) -> &'static [SampleFormat] {
+    match format {
+        OutputFormat::Wav => &[SampleFormat::U8, SampleFormat::S16, SampleFormat::S24, SampleFormat::S32, SampleFormat::F32],
+        OutputFormat::Flac | OutputFormat::Alac => &[SampleFormat::S16, SampleFormat::S24],
+        _ => &[],
+    }
+}
+
+fn compression_range(format: OutputFormat) -> Option<u32> {
+    match format {
+        OutputFormat::Flac => Some(12),
+        OutputFormat::Alac => Some(2),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_args() {
+        let options = ConversionOptions::new();
+        let args = options.encoder_args(OutputFormat::Aac, "out.m4a").unwrap();
+        assert_eq!(args, ["-acodec", "aac", "-ab", "192k", "-ar", "44100", "-f", "ipod"]);
+
+        let args = options.encoder_args(OutputFormat::Flac, "out.flac").unwrap();
+        assert_eq!(args, ["-acodec", "flac", "-f", "flac"]);
+    }
+
+    #[test]
+    fn test_speech_and_music_presets() {
+        let speech = ConversionOptions::new().bitrate(64).channels(1);
+        let args = speech.encoder_args(OutputFormat::Mp3, "talk.mp3").unwrap();
+        assert_eq!(args, ["-acodec", "libmp3lame", "-ab", "64k", "-ar", "44100", "-ac", "1", "-f", "mp3"]);
+
+        let music = ConversionOptions::new().bitrate(320).channels(2).sample_rate(48000);
+        let args = music.encoder_args(OutputFormat::Mp3, "song.mp3").unwrap();
+        assert_eq!(args, ["-acodec", "libmp3lame", "-ab", "320k", "-ar", "48000", "-ac", "2", "-f", "mp3"]);
+    }
+
+    #[test]
+    fn test_lossless_args() {
+        let options = ConversionOptions::new().sample_format(SampleFormat::S24).compression_level(8);
+        let args = options.encoder_args(OutputFormat::Flac, "out.flac").unwrap();
+        assert_eq!(
+            args,
+            ["-acodec", "flac", "-sample_fmt", "s32", "-bits_per_raw_sample", "24", "-compression_level", "8", "-f", "flac"]
+        );
+
+        let options = ConversionOptions::new().sample_format(SampleFormat::F32);
+        let args = options.encoder_args(OutputFormat::Wav, "out.wav").unwrap();
+        assert_eq!(args, ["-acodec", "pcm_f32le", "-f", "wav"]);
+    }
+
+    #[test]
+    fn test_stream_args() {
+        let options = ConversionOptions::new().bitrate(96);
+        let args = options.stream_encoder_args(OutputFormat::Aac).unwrap();
+        assert_eq!(args, ["-acodec", "aac", "-ab", "96k", "-ar", "44100", "-f", "adts"]);
+
+        let args = options.stream_encoder_args(OutputFormat::Opus).unwrap();
+        assert_eq!(args.last().map(String::as_str), Some("opus"));
+        assert!(matches!(options.stream_encoder_args(OutputFormat::Alac), Err(ConversionError::InvalidFormat(_))));
+    }
+
+    #[test]
+    fn test_encoder_from_capabilities() {
+        let with = |encoders: &[&str], muxers: &[&str]| {
+            let list = |names: &[&str]| format!("--\n{}", names.iter().map(|n| format!(" A..... {} x\n", n)).collect::<String>());
+            Capabilities::parse(&list(encoders), &list(muxers))
+        };
+        let fdk = with(&["libfdk_aac", "aac"], &["ipod"]);
+        let args = ConversionOptions::new().encoder_args_for(OutputFormat::Aac, "out.m4a", Some(&fdk)).unwrap();
+        assert_eq!(args[..2], ["-acodec", "libfdk_aac"]);
+        let args = ConversionOptions::new().vbr_quality(1.0).encoder_args_for(OutputFormat::Aac, "out.m4a", Some(&fdk)).unwrap();
+        assert_eq!(args[..2], ["-acodec", "aac"]);
+
+        let native_opus = with(&["opus"], &["ogg"]);
+        let args = ConversionOptions::new().encoder_args_for(OutputFormat::Opus, "out.ogg", Some(&native_opus)).unwrap();
+        assert_eq!(args[..4], ["-acodec", "opus", "-strict", "experimental"]);
+        let result = ConversionOptions::new().encoder_args_for(OutputFormat::Opus, "out.webm", Some(&native_opus));
+        assert!(matches!(result, Err(ConversionError::EncoderUnavailable(ref m)) if m.contains("'webm' container")));
+        let result = ConversionOptions::new().encoder_args_for(OutputFormat::Mp3, "out.mp3", Some(&native_opus));
+        assert!(matches!(result, Err(ConversionError::EncoderUnavailable(_))));
+    }
+
+    #[test]
+    fn test_trim() {
+        let options = ConversionOptions::new().start(750.0).end(2700.0);
+        assert_eq!(options.trimmed_duration(3600.0).unwrap(), 1950.0);
+        let (input, output) = options.trim_args(true).unwrap();
+        assert_eq!(input, ["-ss", "750", "-t", "1950"]);
+        assert!(output.is_empty());
+
+        let options = ConversionOptions::new().start(30.0).duration(600.0);
+        // Clamped to what is left of the input
+        assert_eq!(options.trimmed_duration(120.0).unwrap(), 90.0);
+        // An unknown input length is still bounded by the trim
+        assert_eq!(options.output_duration(None).unwrap(), Some(600.0));
+        assert_eq!(ConversionOptions::new().start(30.0).output_duration(None).unwrap(), None);
+        let (input, output) = options.trim_args(false).unwrap();
+        assert!(input.is_empty());
+        assert_eq!(output, ["-ss", "30", "-t", "600"]);
+
+        let invalid = |options: ConversionOptions| {
+            matches!(options.trimmed_duration(100.0), Err(ConversionError::InvalidOptions(_)))
+        };
+        assert!(invalid(ConversionOptions::new().start(50.0).end(40.0)));
+        assert!(invalid(ConversionOptions::new().end(40.0).duration(10.0)));
+        assert!(invalid(ConversionOptions::new().start(100.0)));
+        assert!(invalid(ConversionOptions::new().start(-1.0)));
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("00:12:30").unwrap(), 750.0);
+        assert_eq!(parse_timestamp("45:00").unwrap(), 2700.0);
+        assert_eq!(parse_timestamp("90.5").unwrap(), 90.5);
+        assert_eq!(parse_timestamp("1:02:03.25").unwrap(), 3723.25);
+        assert!(parse_timestamp("1:75").is_err());
+        assert!(parse_timestamp("1.5:00").is_err());
+        assert!(parse_timestamp("abc").is_err());
+        assert_eq!(format_timestamp(3723.25), "01:02:03.250");
+    }
+
+    #[test]
+    fn test_validation() {
+        let invalid = |options: ConversionOptions, format| {
+            matches!(options.validate(format), Err(ConversionError::InvalidOptions(_)))
+        };
+
+        assert!(invalid(ConversionOptions::new().bitrate(192), OutputFormat::Flac));
+        assert!(invalid(ConversionOptions::new().bitrate(400), OutputFormat::Mp3));
+        assert!(invalid(ConversionOptions::new().bitrate(128).vbr_quality(2.0), OutputFormat::Mp3));
+        assert!(invalid(ConversionOptions::new().vbr_quality(5.0), OutputFormat::Opus));
+        assert!(invalid(ConversionOptions::new().sample_rate(44100), OutputFormat::Opus));
+        assert!(invalid(ConversionOptions::new().channels(6), OutputFormat::Mp3));
+        assert!(invalid(ConversionOptions::new().sample_format(SampleFormat::S24), OutputFormat::Mp3));
+        assert!(invalid(ConversionOptions::new().compression_level(5), OutputFormat::Alac));
+        assert!(invalid(ConversionOptions::new().compression_level(5), OutputFormat::Mp3));
+
+        assert!(ConversionOptions::new().vbr_quality(2.0).validate(OutputFormat::Mp3).is_ok());
+        assert!(ConversionOptions::new().channels(6).validate(OutputFormat::Ac3).is_ok());
+    }
+}
@@ -0,0 +1,350 @@
+use std::collections::HashMap;
+use std::process::{Command, Output, Stdio};
+
+use serde::Deserialize;
+
+use crate::ConversionError;
+
+/// What a stream of the input carries, from ffprobe's `codec_type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamKind {
+    /// An audio stream.
+    Audio,
+    /// A video stream, including cover art.
+    Video,
+    /// Subtitles.
+    Subtitle,
+    /// Data such as timecodes.
+    Data,
+    /// An attached file such as a font.
+    Attachment,
+    /// Anything ffprobe does not classify.
+    Unknown,
+}
+
+/// How the input marks a stream, from ffprobe's `disposition` flags.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Disposition {
+    /// Played unless another stream is chosen.
+    pub default: bool,
+    /// Meant to be shown even when not chosen, e.g. forced subtitles.
+    pub forced: bool,
+    /// A dubbed track.
+    pub dub: bool,
+    /// The original-language track.
+    pub original: bool,
+    /// A commentary track.
+    pub comment: bool,
+    /// For hearing impaired audiences.
+    pub hearing_impaired: bool,
+    /// For visually impaired audiences, e.g. audio description.
+    pub visual_impaired: bool,
+}
+
+/// One stream of the input.
+#[derive(Debug, Clone)]
+pub struct StreamInfo {
+    /// Absolute stream index in the input, as used by `-map 0:<index>`.
+    pub index: usize,
+    /// What the stream carries.
+    pub kind: StreamKind,
+    /// ffmpeg's name for its codec, e.g. `aac`.
+    pub codec: Option<String>,
+    /// Language tag, usually an ISO 639-2 code.
+    pub language: Option<String>,
+    /// Title tag.
+    pub title: Option<String>,
+    /// Number of audio channels.
+    pub channels: Option<u32>,
+    /// Channel layout, e.g. `stereo` or `5.1(side)`.
+    pub channel_layout: Option<String>,
+    /// Audio sample rate in Hz.
+    pub sample_rate: Option<u32>,
+    /// Bitrate in bit/s.
+    pub bitrate: Option<u64>,
+    /// Length of the stream in seconds, if the container reports one.
+    pub duration_seconds: Option<f64>,
+    /// How the input marks the stream.
+    pub disposition: Disposition,
+    /// Every tag of the stream.
+    pub tags: HashMap<String, String>,
+}
+
+impl StreamInfo {
+    /// Whether this is an audio stream.
+    pub fn is_audio(&self) -> bool {
+        self.kind == StreamKind::Audio
+    }
+}
+
+/// A chapter marker of the input.
+#[derive(Debug, Clone)]
+pub struct Chapter {
+    /// ID from the container.
+    pub id: i64,
+    /// Start, in seconds from the start of the input.
+    pub start_seconds: f64,
+    /// End, in seconds.
+    pub end_seconds: f64,
+    /// Title, unless it is missing or blank.
+    pub title: Option<String>,
+}
+
+impl Chapter {
+    /// Length of the chapter in seconds, never negative.
+    pub fn duration_seconds(&self) -> f64 {
+        (self.end_seconds - self.start_seconds).max(0.0)
+    }
+}
+
+/// What ffprobe reports about an input.
+#[derive(Debug, Clone, Default)]
+pub struct MediaInfo {
+    /// ffprobe's `format_name`, e.g. `mov,mp4,m4a,3gp,3g2,mj2`.
+    pub container: String,
+    /// `None` when the container doesn't report one (live streams, some
+    /// WebM, pipes).
+    pub duration_seconds: Option<f64>,
+    /// Overall bitrate in bit/s.
+    pub bitrate: Option<u64>,
+    /// Every stream, in input order.
+    pub streams: Vec<StreamInfo>,
+    /// Chapter markers, in input order.
+    pub chapters: Vec<Chapter>,
+    /// Container-level tags such as title and artist.
+    pub tags: HashMap<String, String>,
+}
+
+impl MediaInfo {
+    /// Parses the output of `ffprobe -print_format json -show_format
+    /// -show_streams -show_chapters`.
+    pub fn from_json(json: &str) -> Result<Self, ConversionError> {
+        let raw: RawOutput = serde_json::from_str(json)
+            .map_err(|e| ConversionError::InvalidFormat(format!("unreadable ffprobe output: {}", e)))?;
+
+        let format = raw.format.unwrap_or_default();
+        let streams = raw.streams.into_iter().map(StreamInfo::from).collect();
+        let chapters = raw
+            .chapters
+            .into_iter()
+            .filter_map(|c| {
+                Some(Chapter {
+                    id: c.id,
+                    start_seconds: parse_number(c.start_time.as_deref())?,
+                    end_seconds: parse_number(c.end_time.as_deref())?,
+                    title: find_tag(&c.tags, "title").filter(|t| !t.trim().is_empty()),
+                })
+            })
+            .collect();
+
+        Ok(Self {
+            container: format.format_name.unwrap_or_default(),
+            duration_seconds: parse_number(format.duration.as_deref()),
+            bitrate: parse_number(format.bit_rate.as_deref()),
+            streams,
+            chapters,
+            tags: format.tags,
+        })
+    }
+
+    /// The audio streams, in input order.
+    pub fn audio_streams(&self) -> impl Iterator<Item = &StreamInfo> {
+        self.streams.iter().filter(|s| s.is_audio())
+    }
+
+    /// Whether the input has any audio stream.
+    pub fn has_audio(&self) -> bool {
+        self.audio_streams().next().is_some()
+    }
+
+    /// Whether `-ss` before `-i` lands accurately. Elementary streams and
+    /// MPEG program/transport streams have no index to seek with.
+    pub fn supports_fast_seek(&self) -> bool {
+        const UNINDEXED: &[&str] = &["mpegts", "mpeg", "mpegvideo", "h264", "hevc", "aac", "ac3", "loas", "dv"];
+        self.duration_seconds.is_some() && !self.container.split(',').any(|name| UNINDEXED.contains(&name))
+    }
+}
+
+/// Runs the ffprobe at `ffprobe_path` on `input_path`. Inputs it cannot read
+/// fail with `InvalidFormat`.
+pub fn probe(ffprobe_path: &str, input_path: &str) -> Result<MediaInfo, ConversionError> {
+    let output = Command::new(ffprobe_path)
+        .args(probe_args(input_path))
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| ConversionError::IOError(e.to_string()))?;
+    from_output(output)
+}
+
+// ffprobe's JSON report of the container, streams and chapters
+pub(crate) fn probe_args(input_path: &str) -> [&str; 8] {
+    ["-v", "error", "-print_format", "json", "-show_format", "-show_streams", "-show_chapters", input_path]
+}
+
+// Reads the media info from a finished ffprobe run
+pub(crate) fn from_output(output: Output) -> Result<MediaInfo, ConversionError> {
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(ConversionError::InvalidFormat(stderr.trim().to_string()));
+    }
+
+    MediaInfo::from_json(&String::from_utf8_lossy(&output.stdout))
+}
+
+// ffprobe reports numbers as strings and uses "N/A" for unknown values
+fn parse_number<T: std::str::FromStr>(value: Option<&str>) -> Option<T> {
+    value.and_then(|v| v.trim().parse().ok())
+}
+
+fn find_tag(tags: &HashMap<String, String>, key: &str) -> Option<String> {
+    tags.iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(key))
+        .map(|(_, v)| v.clone())
+}
+
+#[derive(Deserialize)]
+struct RawOutput {
+    #[serde(default)]
+    streams: Vec<RawStream>,
+    #[serde(default)]
+    chapters: Vec<RawChapter>,
+    format: Option<RawFormat>,
+}
+
+#[derive(Deserialize)]
+struct RawChapter {
+    id: i64,
+    start_time: Option<String>,
+    end_time: Option<String>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+}
+
+#[derive(Deserialize, Default)]
+struct RawFormat {
+    format_name: Option<String>,
+    duration: Option<String>,
+    bit_rate: Option<String>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct RawStream {
+    index: usize,
+    codec_type: Option<String>,
+    codec_name: Option<String>,
+    channels: Option<u32>,
+    channel_layout: Option<String>,
+    sample_rate: Option<String>,
+    bit_rate: Option<String>,
+    duration: Option<String>,
+    #[serde(default)]
+    disposition: HashMap<String, i64>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+}
+
+impl From<RawStream> for StreamInfo {
+    fn from(raw: RawStream) -> Self {
+        let kind = match raw.codec_type.as_deref() {
+            Some("audio") => StreamKind::Audio,
+            Some("video") => StreamKind::Video,
+            Some("subtitle") => StreamKind::Subtitle,
+            Some("data") => StreamKind::Data,
+            Some("attachment") => StreamKind::Attachment,
+            _ => StreamKind::Unknown,
+        };
+        let flag = |name: &str| raw.disposition.get(name).copied().unwrap_or(0) != 0;
+        let disposition = Disposition {
+            default: flag("default"),
+            forced: flag("forced"),
+            dub: flag("dub"),
+            original: flag("original"),
+            comment: flag("comment"),
+            hearing_impaired: flag("hearing_impaired"),
+            visual_impaired: flag("visual_impaired"),
+        };
+
+        Self {
+            index: raw.index,
+            kind,
+            codec: raw.codec_name,
+            language: find_tag(&raw.tags, "language").filter(|l| l != "und"),
+            title: find_tag(&raw.tags, "title"),
+            channels: raw.channels,
+            channel_layout: raw.channel_layout,
+            sample_rate: parse_number(raw.sample_rate.as_deref()),
+            bitrate: parse_number(raw.bit_rate.as_deref()),
+            duration_seconds: parse_number(raw.duration.as_deref()),
+            disposition,
+            tags: raw.tags,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MKV_JSON: &str = r#"{
+        "streams": [
+            {"index": 0, "codec_name": "h264", "codec_type": "video",
+             "disposition": {"default": 1}, "tags": {"DURATION": "00:42:00.000000000"}},
+            {"index": 1, "codec_name": "ac3", "codec_type": "audio", "sample_rate": "48000",
+             "channels": 6, "channel_layout": "5.1(side)", "bit_rate": "448000",
+             "disposition": {"default": 1, "comment": 0}, "tags": {"language": "eng", "title": "Main"}},
+            {"index": 2, "codec_name": "aac", "codec_type": "audio", "sample_rate": "44100",
+             "channels": 2, "disposition": {"default": 0, "comment": 1},
+             "tags": {"LANGUAGE": "ger", "title": "Director's commentary"}},
+            {"index": 3, "codec_name": "subrip", "codec_type": "subtitle", "tags": {"language": "und"}}
+        ],
+        "chapters": [
+            {"id": 1, "time_base": "1/1000000000", "start": 0, "start_time": "0.000000",
+             "end": 90000000000, "end_time": "90.000000", "tags": {"title": "Opening"}},
+            {"id": 2, "time_base": "1/1000000000", "start": 90000000000, "start_time": "90.000000",
+             "end": 362412250000000, "end_time": "362412.250000"}
+        ],
+        "format": {"format_name": "matroska,webm", "duration": "362412.250000",
+                   "bit_rate": "5123456", "tags": {"title": "Movie"}}
+    }"#;
+
+    #[test]
+    fn test_parse_media_info() {
+        let info = MediaInfo::from_json(MKV_JSON).unwrap();
+        assert_eq!(info.container, "matroska,webm");
+        // Longer than 99 hours, which the old stderr regex could not read
+        assert_eq!(info.duration_seconds, Some(362412.25));
+        assert_eq!(info.bitrate, Some(5123456));
+        assert_eq!(info.streams.len(), 4);
+        assert_eq!(info.audio_streams().count(), 2);
+
+        let main = &info.streams[1];
+        assert_eq!(main.codec.as_deref(), Some("ac3"));
+        assert_eq!(main.language.as_deref(), Some("eng"));
+        assert_eq!(main.channels, Some(6));
+        assert_eq!(main.sample_rate, Some(48000));
+        assert!(main.disposition.default);
+
+        let commentary = &info.streams[2];
+        assert_eq!(commentary.language.as_deref(), Some("ger"));
+        assert!(commentary.disposition.comment);
+        assert!(!commentary.disposition.default);
+
+        assert_eq!(info.chapters.len(), 2);
+        assert_eq!(info.chapters[0].title.as_deref(), Some("Opening"));
+        assert_eq!(info.chapters[0].duration_seconds(), 90.0);
+        assert_eq!(info.chapters[1].title, None);
+
+        assert_eq!(info.streams[3].kind, StreamKind::Subtitle);
+        assert_eq!(info.streams[3].language, None);
+    }
+
+    #[test]
+    fn test_unknown_duration() {
+        let json = r#"{"streams": [], "format": {"format_name": "mpegts", "duration": "N/A"}}"#;
+        let info = MediaInfo::from_json(json).unwrap();
+        assert_eq!(info.duration_seconds, None);
+        assert!(!info.has_audio());
+    }
+}
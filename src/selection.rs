@@ -0,0 +1,238 @@
+use std::fmt;
+use std::str::FromStr;
+
+use regex::Regex;
+
+use crate::ConversionError;
+use crate::probe::{MediaInfo, StreamInfo};
+
+/// Picks one of an input's audio streams.
+///
+/// Parses from `"3"`, `"a:1"`, `"lang:eng"` (or just `"eng"`),
+/// `"title:<regex>"` and `"default"`.
+#[derive(Debug, Clone)]
+pub enum StreamSelector {
+    /// Absolute stream index in the input ([`StreamInfo::index`]).
+    Index(usize),
+    /// Nth audio stream, counting from 0, like ffmpeg's `0:a:N`.
+    AudioIndex(usize),
+    /// ISO 639-1 or 639-2 code; "en", "eng" all match each other.
+    Language(String),
+    /// The first stream whose title matches.
+    Title(Regex),
+    /// The stream marked as default.
+    Default,
+}
+
+impl StreamSelector {
+    /// The audio stream of `info` this selector picks, failing with
+    /// `StreamNotFound` (listing the available streams) if none matches.
+    pub fn select<'a>(&self, info: &'a MediaInfo) -> Result<&'a StreamInfo, ConversionError> {
+        let audio: Vec<&StreamInfo> = info.audio_streams().collect();
+        if audio.is_empty() {
+            return Err(ConversionError::StreamNotFound("input has no audio streams".to_string()));
+        }
+
+        let found = match self {
+            StreamSelector::Index(index) => audio.iter().find(|s| s.index == *index).copied(),
+            StreamSelector::AudioIndex(n) => audio.get(*n).copied(),
+            StreamSelector::Language(code) => {
+                let matching: Vec<&StreamInfo> = audio
+                    .iter()
+                    .filter(|s| s.language.as_deref().is_some_and(|l| same_language(l, code)))
+                    .copied()
+                    .collect();
+                // Prefer the main track over commentary when a language has several
+                matching
+                    .iter()
+                    .find(|s| s.disposition.default)
+                    .or_else(|| matching.iter().find(|s| !s.disposition.comment))
+                    .or_else(|| matching.first())
+                    .copied()
+            }
+            StreamSelector::Title(pattern) => audio
+                .iter()
+                .find(|s| s.title.as_deref().is_some_and(|t| pattern.is_match(t)))
+                .copied(),
+            StreamSelector::Default => audio.iter().find(|s| s.disposition.default).copied(),
+        };
+
+        found.ok_or_else(|| {
+            let available: Vec<String> = audio.iter().map(|s| describe(s)).collect();
+            ConversionError::StreamNotFound(format!(
+                "no audio stream matches {} (available: {})",
+                self,
+                available.join(", ")
+            ))
+        })
+    }
+
+    /// ffmpeg `-map` specifier for selectors that work without probing the
+    /// input, which is all a piped input allows. Language and title matching
+    /// need the stream metadata, so they have none.
+    pub fn map_specifier(&self) -> Option<String> {
+        match self {
+            StreamSelector::Index(index) => Some(format!("0:{}", index)),
+            StreamSelector::AudioIndex(n) => Some(format!("0:a:{}", n)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for StreamSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StreamSelector::Index(index) => write!(f, "stream index {}", index),
+            StreamSelector::AudioIndex(n) => write!(f, "audio track {}", n),
+            StreamSelector::Language(code) => write!(f, "language '{}'", code),
+            StreamSelector::Title(pattern) => write!(f, "title /{}/", pattern),
+            StreamSelector::Default => write!(f, "default disposition"),
+        }
+    }
+}
+
+// Accepts "3", "a:1", "lang:eng", "eng", "title:<regex>" and "default"
+impl FromStr for StreamSelector {
+    type Err = ConversionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ConversionError::InvalidOptions(format!("invalid stream selector '{}'", s));
+
+        if s == "default" {
+            return Ok(StreamSelector::Default);
+        }
+        if let Ok(index) = s.parse::<usize>() {
+            return Ok(StreamSelector::Index(index));
+        }
+        if let Some(n) = s.strip_prefix("a:") {
+            return n.parse().map(StreamSelector::AudioIndex).map_err(|_| invalid());
+        }
+        if let Some(pattern) = s.strip_prefix("title:") {
+            return Regex::new(pattern).map(StreamSelector::Title).map_err(|_| invalid());
+        }
+        let code = s.strip_prefix("lang:").unwrap_or(s);
+        if (2..=3).contains(&code.len()) && code.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Ok(StreamSelector::Language(code.to_ascii_lowercase()));
+        }
+        Err(invalid())
+    }
+}
+
+fn describe(stream: &StreamInfo) -> String {
+    let mut text = format!("#{}", stream.index);
+    if let Some(language) = &stream.language {
+        text.push_str(&format!(" {}", language));
+    }
+    if let Some(title) = &stream.title {
+        text.push_str(&format!(" \"{}\"", title));
+    }
+    text
+}
+
+// ISO 639-1 code, 639-2/T code, 639-2/B code (when different)
+const LANGUAGES: &[(&str, &str, &str)] = &[
+    ("ar", "ara", ""),
+    ("cs", "ces", "cze"),
+    ("da", "dan", ""),
+    ("de", "deu", "ger"),
+    ("el", "ell", "gre"),
+    ("en", "eng", ""),
+    ("es", "spa", ""),
+    ("fa", "fas", "per"),
+    ("fi", "fin", ""),
+    ("fr", "fra", "fre"),
+    ("he", "heb", ""),
+    ("hi", "hin", ""),
+    ("hu", "hun", ""),
+    ("it", "ita", ""),
+    ("ja", "jpn", ""),
+    ("ko", "kor", ""),
+    ("nl", "nld", "dut"),
+    ("no", "nor", ""),
+    ("pl", "pol", ""),
+    ("pt", "por", ""),
+    ("ro", "ron", "rum"),
+    ("ru", "rus", ""),
+    ("sv", "swe", ""),
+    ("sw", "swa", ""),
+    ("th", "tha", ""),
+    ("tr", "tur", ""),
+    ("uk", "ukr", ""),
+    ("vi", "vie", ""),
+    ("zh", "zho", "chi"),
+];
+
+pub fn same_language(a: &str, b: &str) -> bool {
+    if a.eq_ignore_ascii_case(b) {
+        return true;
+    }
+    let a = a.to_ascii_lowercase();
+    let b = b.to_ascii_lowercase();
+    LANGUAGES.iter().any(|(one, two_t, two_b)| {
+        let codes = [*one, *two_t, *two_b];
+        let known = |c: &str| !c.is_empty() && codes.contains(&c);
+        known(&a) && known(&b)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn media() -> MediaInfo {
+        MediaInfo::from_json(
+            r#"{
+            "streams": [
+                {"index": 0, "codec_type": "video"},
+                {"index": 1, "codec_type": "audio", "disposition": {"default": 1},
+                 "tags": {"language": "eng", "title": "Main"}},
+                {"index": 2, "codec_type": "audio", "disposition": {"comment": 1},
+                 "tags": {"language": "eng", "title": "Commentary"}},
+                {"index": 3, "codec_type": "audio", "tags": {"language": "ger", "title": "Deutsch"}}
+            ],
+            "format": {"format_name": "matroska,webm", "duration": "60.0"}
+        }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_select_streams() {
+        let info = media();
+        let select = |s: &str| s.parse::<StreamSelector>().unwrap().select(&info).map(|s| s.index);
+
+        assert_eq!(select("2").unwrap(), 2);
+        assert_eq!(select("a:2").unwrap(), 3);
+        assert_eq!(select("de").unwrap(), 3);
+        assert_eq!(select("lang:deu").unwrap(), 3);
+        assert_eq!(select("eng").unwrap(), 1);
+        assert_eq!(select("title:(?i)comment").unwrap(), 2);
+        assert_eq!(select("default").unwrap(), 1);
+    }
+
+    #[test]
+    fn test_no_matching_stream() {
+        let info = media();
+        for selector in ["0", "a:5", "fra", "title:Isolated"] {
+            let result = selector.parse::<StreamSelector>().unwrap().select(&info);
+            assert!(matches!(result, Err(ConversionError::StreamNotFound(_))), "{}", selector);
+        }
+    }
+
+    #[test]
+    fn test_map_specifier() {
+        let specifier = |s: &str| s.parse::<StreamSelector>().unwrap().map_specifier();
+        assert_eq!(specifier("2").as_deref(), Some("0:2"));
+        assert_eq!(specifier("a:1").as_deref(), Some("0:a:1"));
+        assert_eq!(specifier("eng"), None);
+        assert_eq!(specifier("default"), None);
+    }
+
+    #[test]
+    fn test_language_aliases() {
+        assert!(same_language("fre", "fra"));
+        assert!(same_language("FR", "fre"));
+        assert!(!same_language("eng", "ger"));
+        assert!(!same_language("xx", "yy"));
+    }
+}
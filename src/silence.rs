@@ -0,0 +1,208 @@
+use regex::Regex;
+
+use crate::ConversionError;
+use crate::probe::Chapter;
+
+/// A stretch of the input ffmpeg's `silencedetect` found silent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SilenceInterval {
+    /// Where the silence starts, in seconds from the start of the input.
+    pub start_seconds: f64,
+    /// Where it ends, in seconds.
+    pub end_seconds: f64,
+}
+
+impl SilenceInterval {
+    /// Length of the silence, in seconds.
+    pub fn duration_seconds(&self) -> f64 {
+        self.end_seconds - self.start_seconds
+    }
+}
+
+/// How silence is detected, and which silences a split uses.
+#[derive(Debug, Clone)]
+pub struct SilenceOptions {
+    noise_db: f64,
+    min_silence_seconds: f64,
+    min_segment_seconds: f64,
+}
+
+impl Default for SilenceOptions {
+    fn default() -> Self {
+        Self {
+            noise_db: -30.0,
+            min_silence_seconds: 0.5,
+            min_segment_seconds: 0.0,
+        }
+    }
+}
+
+impl SilenceOptions {
+    /// The defaults: -30 dB, at least 0.5s of silence, no minimum segment.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Level below which audio counts as silence, in dBFS (default -30).
+    pub fn noise_threshold(mut self, db: f64) -> Self {
+        self.noise_db = db;
+        self
+    }
+
+    /// Shortest gap that counts as a split point (default 0.5s).
+    pub fn min_silence(mut self, seconds: f64) -> Self {
+        self.min_silence_seconds = seconds;
+        self
+    }
+
+    /// Split points that would leave a shorter segment are skipped
+    /// (default 0).
+    pub fn min_segment(mut self, seconds: f64) -> Self {
+        self.min_segment_seconds = seconds;
+        self
+    }
+
+    /// Fails with `InvalidOptions` for a threshold above 0 dB or a
+    /// non-positive silence length.
+    pub fn validate(&self) -> Result<(), ConversionError> {
+        if self.noise_db > 0.0 {
+            return Err(ConversionError::InvalidOptions("silence threshold must be at most 0 dB".to_string()));
+        }
+        if self.min_silence_seconds <= 0.0 {
+            return Err(ConversionError::InvalidOptions("minimum silence length must be positive".to_string()));
+        }
+        if self.min_segment_seconds < 0.0 {
+            return Err(ConversionError::InvalidOptions("minimum segment length cannot be negative".to_string()));
+        }
+        Ok(())
+    }
+
+    /// The `silencedetect` filter for these options.
+    pub fn filter(&self) -> String {
+        format!("silencedetect=noise={}dB:d={}", self.noise_db, self.min_silence_seconds)
+    }
+
+    /// Turns detected silences into the audible segments between them. Each
+    /// cut lands in the middle of a gap; leading and trailing silence is
+    /// dropped.
+    pub fn segments(&self, silences: &[SilenceInterval], total_seconds: f64) -> Vec<Chapter> {
+        const EDGE: f64 = 0.01;
+
+        let mut start = 0.0;
+        let mut end = total_seconds;
+        let mut cuts = Vec::new();
+        for silence in silences {
+            if silence.start_seconds <= EDGE {
+                start = silence.end_seconds;
+            } else if silence.end_seconds >= total_seconds - EDGE {
+                end = silence.start_seconds;
+            } else {
+                cuts.push((silence.start_seconds + silence.end_seconds) / 2.0);
+            }
+        }
+
+        let mut boundaries = vec![start];
+        for cut in cuts {
+            if cut - boundaries[boundaries.len() - 1] >= self.min_segment_seconds && cut < end {
+                boundaries.push(cut);
+            }
+        }
+        // A too-short tail is merged into the segment before it
+        if boundaries.len() > 1 && end - boundaries[boundaries.len() - 1] < self.min_segment_seconds {
+            boundaries.pop();
+        }
+        boundaries.push(end);
+
+        boundaries
+            .windows(2)
+            .enumerate()
+            .filter(|(_, pair)| pair[1] > pair[0])
+            .map(|(i, pair)| Chapter {
+                id: i as i64,
+                start_seconds: pair[0],
+                end_seconds: pair[1],
+                title: Some(format!("Part {}", i + 1)),
+            })
+            .collect()
+    }
+}
+
+// Reads silencedetect events from ffmpeg's stderr. A silence that runs to the
+// end of the input has no silence_end line and is closed at `total_seconds`.
+pub fn parse_silencedetect(stderr: &str, total_seconds: f64) -> Vec<SilenceInterval> {
+    let start_regex = Regex::new(r"silence_start: (-?[0-9.]+)").unwrap();
+    let end_regex = Regex::new(r"silence_end: (-?[0-9.]+)").unwrap();
+
+    let mut silences = Vec::new();
+    let mut open: Option<f64> = None;
+    for line in stderr.lines() {
+        if let Some(captures) = start_regex.captures(line)
+            && let Ok(start) = captures[1].parse::<f64>()
+        {
+            open = Some(start.max(0.0));
+        } else if let Some(captures) = end_regex.captures(line)
+            && let Ok(end) = captures[1].parse::<f64>()
+        {
+            let start = open.take().unwrap_or(0.0);
+            silences.push(SilenceInterval { start_seconds: start, end_seconds: end });
+        }
+    }
+    if let Some(start) = open
+        && start < total_seconds
+    {
+        silences.push(SilenceInterval { start_seconds: start, end_seconds: total_seconds });
+    }
+
+    silences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STDERR: &str = "\
+[silencedetect @ 0x55d5c8f0] silence_start: 0
+[silencedetect @ 0x55d5c8f0] silence_end: 1.5 | silence_duration: 1.5
+size=N/A time=00:01:00.00 bitrate=N/A speed= 120x
+[silencedetect @ 0x55d5c8f0] silence_start: 62.25
+[silencedetect @ 0x55d5c8f0] silence_end: 64.25 | silence_duration: 2
+[silencedetect @ 0x55d5c8f0] silence_start: 70
+[silencedetect @ 0x55d5c8f0] silence_end: 71 | silence_duration: 1
+[silencedetect @ 0x55d5c8f0] silence_start: 178.5
+";
+
+    #[test]
+    fn test_parse_events() {
+        let silences = parse_silencedetect(STDERR, 180.0);
+        assert_eq!(
+            silences,
+            [
+                SilenceInterval { start_seconds: 0.0, end_seconds: 1.5 },
+                SilenceInterval { start_seconds: 62.25, end_seconds: 64.25 },
+                SilenceInterval { start_seconds: 70.0, end_seconds: 71.0 },
+                SilenceInterval { start_seconds: 178.5, end_seconds: 180.0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_segments() {
+        let silences = parse_silencedetect(STDERR, 180.0);
+        let bounds = |options: SilenceOptions| -> Vec<(f64, f64)> {
+            options.segments(&silences, 180.0).iter().map(|c| (c.start_seconds, c.end_seconds)).collect()
+        };
+
+        assert_eq!(bounds(SilenceOptions::new()), [(1.5, 63.25), (63.25, 70.5), (70.5, 178.5)]);
+        // The 7s segment between the two gaps is too short and gets merged
+        assert_eq!(bounds(SilenceOptions::new().min_segment(10.0)), [(1.5, 63.25), (63.25, 178.5)]);
+        assert_eq!(bounds(SilenceOptions::new().min_segment(500.0)), [(1.5, 178.5)]);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(SilenceOptions::new().validate().is_ok());
+        assert!(SilenceOptions::new().noise_threshold(3.0).validate().is_err());
+        assert!(SilenceOptions::new().min_silence(0.0).validate().is_err());
+        assert_eq!(SilenceOptions::new().noise_threshold(-40.0).min_silence(1.0).filter(), "silencedetect=noise=-40dB:d=1");
+    }
+}
@@ -0,0 +1,223 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::ConversionError;
+use crate::backend::MediaBackend;
+use crate::probe::MediaInfo;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+// An output that ffmpeg writes to a hidden sibling temp file. `commit` moves
+// it over the real path once the run succeeded and the file holds audio;
+// otherwise it is removed on drop, so a failed or killed run never touches
+// an existing output. The temp name keeps the extension ffmpeg picks the
+// container from.
+#[derive(Debug)]
+pub(crate) struct StagedOutput {
+    target: String,
+    temp: String,
+    committed: bool,
+}
+
+impl StagedOutput {
+    pub fn new(target: &str) -> Self {
+        let path = Path::new(target);
+        let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        let mut name = format!(".{}~partial-{}-{}", stem, std::process::id(), NEXT_ID.fetch_add(1, Ordering::Relaxed));
+        if let Some(ext) = path.extension() {
+            name.push('.');
+            name.push_str(&ext.to_string_lossy());
+        }
+        let temp: PathBuf = path.with_file_name(name);
+
+        Self {
+            target: target.to_string(),
+            temp: temp.to_string_lossy().into_owned(),
+            committed: false,
+        }
+    }
+
+    // Where ffmpeg should write
+    pub fn path(&self) -> &str {
+        &self.temp
+    }
+
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    // Checks the output, then renames it into place
+    pub fn commit(self, backend: &dyn MediaBackend) -> Result<(), ConversionError> {
+        self.verify(backend)?;
+        self.rename()
+    }
+
+    // `commit` with the output probed through the backend's async path
+    #[cfg(feature = "async")]
+    pub async fn commit_async(self, backend: &dyn MediaBackend) -> Result<(), ConversionError> {
+        self.check_written()?;
+        self.check_media(backend.probe_async(&self.temp).await)?;
+        self.rename()
+    }
+
+    // A zero exit status is not enough: ffmpeg can finish with an empty or
+    // truncated file, so the output must probe as having an audio stream.
+    fn verify(&self, backend: &dyn MediaBackend) -> Result<(), ConversionError> {
+        self.check_written()?;
+        self.check_media(backend.probe(&self.temp))
+    }
+
+    fn check_written(&self) -> Result<(), ConversionError> {
+        let written = std::fs::metadata(&self.temp).is_ok_and(|metadata| metadata.is_file() && metadata.len() > 0);
+        if !written {
+            return Err(ConversionError::IOError(format!("ffmpeg finished without writing {}", self.target)));
+        }
+        Ok(())
+    }
+
+    fn check_media(&self, probed: Result<MediaInfo, ConversionError>) -> Result<(), ConversionError> {
+        let media_info = probed.map_err(|e| ConversionError::IOError(format!("ffmpeg wrote an unreadable {}: {}", self.target, e)))?;
+        if !media_info.has_audio() {
+            return Err(ConversionError::IOError(format!("ffmpeg wrote {} without an audio stream", self.target)));
+        }
+        Ok(())
+    }
+
+    fn rename(mut self) -> Result<(), ConversionError> {
+        std::fs::rename(&self.temp, &self.target)
+            .map_err(|e| ConversionError::IOError(format!("could not move the output into place at {}: {}", self.target, e)))?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for StagedOutput {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = std::fs::remove_file(&self.temp);
+        }
+    }
+}
+
+// Commits every output of a run: all are checked before any is moved, so a
+// bad output leaves every target untouched. A failed rename can still leave
+// the earlier targets replaced; the error names them.
+pub(crate) fn commit_all(outputs: Vec<StagedOutput>, backend: &dyn MediaBackend) -> Result<(), ConversionError> {
+    for output in &outputs {
+        output.verify(backend)?;
+    }
+
+    let mut replaced = Vec::new();
+    for output in outputs {
+        let target = output.target.clone();
+        match output.rename() {
+            Ok(()) => replaced.push(target),
+            Err(ConversionError::IOError(message)) if !replaced.is_empty() => {
+                return Err(ConversionError::IOError(format!("{} (already replaced: {})", message, replaced.join(", "))));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MediaInfo;
+    use crate::backend::ScriptedBackend;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vac_staging_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn media(codec_type: &str) -> MediaInfo {
+        MediaInfo::from_json(&format!(r#"{{"streams": [{{"index": 0, "codec_type": "{}"}}]}}"#, codec_type)).unwrap()
+    }
+
+    // Stages `target` with `bytes` written and probing as `codec_type`
+    fn stage(backend: ScriptedBackend, target: &str, bytes: &[u8], codec_type: &str) -> (ScriptedBackend, StagedOutput) {
+        let staged = StagedOutput::new(target);
+        std::fs::write(staged.path(), bytes).unwrap();
+        (backend.media(staged.path(), media(codec_type)), staged)
+    }
+
+    #[test]
+    fn test_temp_names() {
+        let staged = StagedOutput::new("out/talk.final.mp3");
+        let temp = Path::new(staged.path());
+        assert_eq!(temp.parent(), Some(Path::new("out")));
+        assert_eq!(temp.extension().unwrap(), "mp3");
+        assert!(temp.file_name().unwrap().to_string_lossy().starts_with(".talk.final~partial-"), "{}", staged.path());
+        assert_ne!(StagedOutput::new("out/talk.final.mp3").path(), staged.path());
+
+        // No extension to keep, and none made up
+        assert!(Path::new(StagedOutput::new("raw").path()).extension().is_none());
+    }
+
+    #[test]
+    fn test_commit_and_discard() {
+        let dir = temp_dir("commit");
+        let target = dir.join("song.flac").to_string_lossy().into_owned();
+        std::fs::write(&target, b"previous").unwrap();
+
+        let staged = StagedOutput::new(&target);
+        std::fs::write(staged.path(), b"partial").unwrap();
+        drop(staged);
+        assert_eq!(std::fs::read(&target).unwrap(), b"previous");
+
+        // An empty file, one that does not probe or one without audio is not
+        // a usable output
+        let (backend, staged) = stage(ScriptedBackend::new(), &target, b"", "audio");
+        assert!(matches!(staged.commit(&backend), Err(ConversionError::IOError(_))));
+        let staged = StagedOutput::new(&target);
+        std::fs::write(staged.path(), b"garbage").unwrap();
+        assert!(matches!(staged.commit(&backend), Err(ConversionError::IOError(_))));
+        let (backend, staged) = stage(backend, &target, b"video", "video");
+        assert!(matches!(staged.commit(&backend), Err(ConversionError::IOError(_))));
+        assert_eq!(std::fs::read(&target).unwrap(), b"previous");
+
+        let (backend, staged) = stage(backend, &target, b"new", "audio");
+        staged.commit(&backend).unwrap();
+        assert_eq!(std::fs::read(&target).unwrap(), b"new");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_commit_all() {
+        let dir = temp_dir("commit_all");
+        let targets: Vec<String> = ["a.wav", "b.wav", "c.wav"].iter().map(|name| dir.join(name).to_string_lossy().into_owned()).collect();
+        for target in &targets {
+            std::fs::write(target, b"previous").unwrap();
+        }
+
+        // One bad output keeps every target as it was
+        let (backend, a) = stage(ScriptedBackend::new(), &targets[0], b"new", "audio");
+        let (backend, b) = stage(backend, &targets[1], b"new", "video");
+        let (backend, c) = stage(backend, &targets[2], b"new", "audio");
+        assert!(matches!(commit_all(vec![a, b, c], &backend), Err(ConversionError::IOError(_))));
+        for target in &targets {
+            assert_eq!(std::fs::read(target).unwrap(), b"previous");
+        }
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 3);
+
+        // A rename that fails after others went through names what changed
+        std::fs::remove_file(&targets[1]).unwrap();
+        std::fs::create_dir_all(Path::new(&targets[1]).join("blocker")).unwrap();
+        let (backend, a) = stage(ScriptedBackend::new(), &targets[0], b"new", "audio");
+        let (backend, b) = stage(backend, &targets[1], b"new", "audio");
+        let (backend, c) = stage(backend, &targets[2], b"new", "audio");
+        let result = commit_all(vec![a, b, c], &backend);
+        assert!(matches!(result, Err(ConversionError::IOError(ref m)) if m.ends_with(&format!("(already replaced: {})", targets[0]))), "{:?}", result);
+        assert_eq!(std::fs::read(&targets[0]).unwrap(), b"new");
+        assert_eq!(std::fs::read(&targets[2]).unwrap(), b"previous");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 3);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,229 @@
+use std::io::{ErrorKind, Read, Write};
+
+use crate::converter::ensure_parent_dir;
+use crate::events::{EventSink, ProgressParser};
+use crate::staging::StagedOutput;
+use crate::{ConversionError, ConversionOptions, ProgressEvent, Stage, VideoToAudioConverter};
+
+const PIPE_BUFFER_SIZE: usize = 64 * 1024;
+
+// Caller-provided streams connected to ffmpeg's stdin and stdout
+#[derive(Default)]
+pub(crate) struct Pipes<'a> {
+    pub input: Option<&'a mut (dyn Read + Send)>,
+    pub output: Option<&'a mut (dyn Write + Send)>,
+}
+
+enum Input<'a> {
+    Path(&'a str),
+    Reader(&'a mut (dyn Read + Send)),
+}
+
+enum Output<'a> {
+    Path(&'a str),
+    Writer(&'a mut (dyn Write + Send)),
+}
+
+impl VideoToAudioConverter {
+    /// Converts audio read from `input`, e.g. an upload held in memory, to a
+    /// file at `output_path`.
+    ///
+    /// The input is piped to ffmpeg and cannot be probed: `Started` carries an
+    /// empty [`MediaInfo`](crate::MediaInfo), progress is indeterminate unless the options trim
+    /// to a known length, and streams can only be selected by index. Loudness
+    /// normalization, which reads the input twice, is not available.
+    ///
+    /// The call returns only once `input` has returned from its last read. A
+    /// reader that blocks, e.g. on a stalled network connection, keeps the call
+    /// waiting even after ffmpeg has exited or been cancelled; give such
+    /// readers a timeout of their own.
+    pub fn convert_reader<R, F>(&self, mut input: R, output_path: &str, options: &ConversionOptions, listener: F) -> Result<(), ConversionError>
+    where
+        R: Read + Send,
+        F: FnMut(&ProgressEvent) + Send + 'static,
+    {
+        let events = EventSink::new(listener);
+        events.outcome(self.do_convert_piped(Input::Reader(&mut input), Output::Path(output_path), options, &events))
+    }
+
+    /// Converts the file at `input_path` and writes the audio to `output`.
+    ///
+    /// The format must be set in `options` and must have a container that can
+    /// be written without seeking, which rules out ALAC. Loudness
+    /// normalization is not available.
+    pub fn convert_to_writer<W, F>(&self, input_path: &str, mut output: W, options: &ConversionOptions, listener: F) -> Result<(), ConversionError>
+    where
+        W: Write + Send,
+        F: FnMut(&ProgressEvent) + Send + 'static,
+    {
+        let events = EventSink::new(listener);
+        events.outcome(self.do_convert_piped(Input::Path(input_path), Output::Writer(&mut output), options, &events))
+    }
+
+    /// Converts audio read from `input` and writes it to `output`, without
+    /// touching the filesystem. The limitations of both
+    /// [`convert_reader`](Self::convert_reader) and
+    /// [`convert_to_writer`](Self::convert_to_writer) apply.
+    pub fn convert_stream<R, W, F>(&self, mut input: R, mut output: W, options: &ConversionOptions, listener: F) -> Result<(), ConversionError>
+    where
+        R: Read + Send,
+        W: Write + Send,
+        F: FnMut(&ProgressEvent) + Send + 'static,
+    {
+        let events = EventSink::new(listener);
+        events.outcome(self.do_convert_piped(Input::Reader(&mut input), Output::Writer(&mut output), options, &events))
+    }
+
+    fn do_convert_piped<'a>(&self, input: Input<'a>, output: Output<'a>, options: &ConversionOptions, events: &EventSink) -> Result<(), ConversionError> {
+        if options.loudness_target().is_some() {
+            return Err(ConversionError::InvalidOptions(
+                "loudness normalization reads the input twice and is not available for streamed conversions".to_string(),
+            ));
+        }
+
+        let (input_arg, media_info, reader) = match input {
+            Input::Path(path) => (path, Some(self.probe(path)?), None),
+            Input::Reader(reader) => ("pipe:0", None, Some(reader)),
+        };
+        let (staged, codec_args, writer) = match output {
+            Output::Path(path) => {
+                let format = options.resolve_format(path)?;
+                let staged = StagedOutput::new(path);
+                let codec_args = options.encoder_args_for(format, staged.path(), Some(&self.capabilities))?;
+                (Some(staged), codec_args, None)
+            }
+            Output::Writer(writer) => {
+                let format = options.format_override().ok_or_else(|| {
+                    ConversionError::InvalidOptions("an output format is required when writing to a stream".to_string())
+                })?;
+                (None, options.stream_encoder_args_for(format, Some(&self.capabilities))?, Some(writer))
+            }
+        };
+        let output_arg = staged.as_ref().map_or("pipe:1", StagedOutput::path);
+
+        events.emit(ProgressEvent::Started {
+            input_path: input_arg.to_string(),
+            media_info: media_info.clone().unwrap_or_default(),
+        });
+        let (args, duration) = self.ffmpeg_command(input_arg, output_arg, &codec_args, options, media_info.as_ref(), &[])?;
+        if staged.is_some() {
+            ensure_parent_dir(output_arg)?;
+        }
+
+        events.emit(ProgressEvent::StageChanged(Stage::Encoding));
+        let pipes = Pipes { input: reader, output: writer };
+        let outputs: Vec<&str> = staged.iter().map(StagedOutput::path).collect();
+        let (_, written) = self.run_ffmpeg_piped(&args, &outputs, options, ProgressParser::new(duration), events, pipes)?;
+
+        match staged {
+            Some(staged) => {
+                let output_path = staged.target().to_string();
+                staged.commit(&*self.backend)?;
+                events.finished(vec![output_path], duration);
+            }
+            None => events.finished_streamed(written, duration),
+        }
+        Ok(())
+    }
+}
+
+// Copies the caller's input into ffmpeg until either side is done. ffmpeg
+// closing its stdin early (e.g. after a trimmed segment) is not an error.
+// Nothing interrupts a read that blocks, so the conversion waits for it.
+pub(crate) fn pump_input(input: &mut (dyn Read + Send), mut stdin: Box<dyn Write + Send>) -> std::io::Result<()> {
+    let mut buffer = vec![0; PIPE_BUFFER_SIZE];
+    loop {
+        let read = match input.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(read) => read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if stdin.write_all(&buffer[..read]).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+pub(crate) fn pump_output(mut stdout: Box<dyn Read + Send>, output: &mut (dyn Write + Send)) -> std::io::Result<u64> {
+    let written = std::io::copy(&mut stdout, output)?;
+    output.flush()?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{ScriptedBackend, ScriptedRun};
+    use crate::{Capabilities, OutputFormat};
+
+    // `sh` stands in for ffmpeg: it echoes stdin to stdout and reports one
+    // progress block on stderr
+    #[cfg(unix)]
+    #[test]
+    fn test_pipes_round_trip() {
+        let converter = VideoToAudioConverter::with_program("sh");
+        let args = ["-c", "cat; echo out_time_us=2000000 >&2; echo progress=end >&2"].map(String::from);
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let events = EventSink::new(crate::events::forward_to(sender));
+        let input = vec![7u8; 3 * PIPE_BUFFER_SIZE + 5];
+        let mut output = Vec::new();
+        let pipes = Pipes { input: Some(&mut input.as_slice()), output: Some(&mut output) };
+        let (_, written) = converter
+            .run_ffmpeg_piped(&args, &[], &ConversionOptions::new(), ProgressParser::new(Some(4.0)), &events, pipes)
+            .unwrap();
+        drop(events);
+
+        assert_eq!(written, input.len() as u64);
+        assert_eq!(output, input);
+        let progress: Vec<_> = receiver.iter().collect();
+        assert!(matches!(&progress[..], [ProgressEvent::Progress(p)] if p.percentage == Some(50.0)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_failed_write_is_reported() {
+        struct Broken;
+        impl Write for Broken {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("disk on fire"))
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let converter = VideoToAudioConverter::with_program("sh");
+        let args = ["-c", "echo audio"].map(String::from);
+        let pipes = Pipes { input: None, output: Some(&mut Broken) };
+        let result = converter.run_ffmpeg_piped(&args, &[], &ConversionOptions::new(), ProgressParser::new(None), &EventSink::new(|_| {}), pipes);
+        assert!(matches!(result, Err(ConversionError::IOError(ref m)) if m == "could not write output: disk on fire"));
+    }
+
+    #[test]
+    fn test_streamed_output_needs_format() {
+        let backend = ScriptedBackend::new();
+        let converter = VideoToAudioConverter::with_backend(backend.clone(), Capabilities::default());
+        let result = converter.convert_stream(&b"not a video"[..], Vec::new(), &ConversionOptions::new(), |_| {});
+        assert!(matches!(result, Err(ConversionError::InvalidOptions(_))));
+
+        let options = ConversionOptions::new().format(OutputFormat::Alac);
+        let result = converter.convert_stream(&b"not a video"[..], Vec::new(), &options, |_| {});
+        assert!(matches!(result, Err(ConversionError::InvalidFormat(_))));
+        assert!(backend.commands().is_empty());
+    }
+
+    #[test]
+    fn test_unreadable_input() {
+        let backend = ScriptedBackend::new()
+            .run(ScriptedRun::new("[in#0 @ 0x5581] Error opening input: Invalid data found when processing input\n").exit_code(1));
+        let capabilities = Capabilities::parse(" ------\n A....D libmp3lame  MP3\n", " ---\n  E  mp3  MP3\n");
+        let converter = VideoToAudioConverter::with_backend(backend, capabilities);
+        let mut output = Vec::new();
+        let options = ConversionOptions::new().format(OutputFormat::Mp3);
+        let result = converter.convert_stream(&b"not a video"[..], &mut output, &options, |_| {});
+        assert!(matches!(result, Err(ConversionError::InvalidData(_))), "{:?}", result);
+        assert!(output.is_empty());
+    }
+}
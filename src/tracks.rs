@@ -0,0 +1,142 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::ConversionError;
+use crate::probe::{MediaInfo, StreamInfo};
+use crate::selection::StreamSelector;
+
+/// One file written by
+/// [`extract_tracks`](crate::VideoToAudioConverter::extract_tracks).
+#[derive(Debug, Clone)]
+pub struct TrackOutput {
+    /// The audio stream it holds.
+    pub stream: StreamInfo,
+    /// 1-based position among the input's audio streams.
+    pub track_number: usize,
+    /// Where it is written.
+    pub output_path: String,
+}
+
+// Expands an output template for one audio stream. Placeholders:
+// {stem} input file name without extension, {index} absolute stream index,
+// {track} 1-based audio track number, {lang} language code ("und" if untagged),
+// {title} stream title ("track<N>" if untitled).
+pub fn render_template(template: &str, input_path: &str, stream: &StreamInfo, track_number: usize) -> String {
+    let stem = Path::new(input_path)
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let title = stream
+        .title
+        .as_deref()
+        .map(sanitize)
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| format!("track{}", track_number));
+
+    template
+        .replace("{stem}", &stem)
+        .replace("{index}", &stream.index.to_string())
+        .replace("{track}", &track_number.to_string())
+        .replace("{lang}", stream.language.as_deref().unwrap_or("und"))
+        .replace("{title}", &title)
+}
+
+// Resolves which audio streams to extract (all of them when `selectors` is empty)
+// and the output file for each.
+pub fn plan_tracks(
+    info: &MediaInfo,
+    input_path: &str,
+    template: &str,
+    selectors: &[StreamSelector],
+) -> Result<Vec<TrackOutput>, ConversionError> {
+    let audio: Vec<&StreamInfo> = info.audio_streams().collect();
+    if audio.is_empty() {
+        return Err(ConversionError::StreamNotFound("input has no audio streams".to_string()));
+    }
+
+    let mut chosen: Vec<usize> = Vec::new();
+    if selectors.is_empty() {
+        chosen.extend(audio.iter().map(|s| s.index));
+    } else {
+        for selector in selectors {
+            let index = selector.select(info)?.index;
+            if !chosen.contains(&index) {
+                chosen.push(index);
+            }
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut tracks = Vec::new();
+    for index in chosen {
+        let position = audio.iter().position(|s| s.index == index).unwrap_or_default();
+        let stream = audio[position].clone();
+        let output_path = render_template(template, input_path, &stream, position + 1);
+
+        if !seen.insert(output_path.clone()) {
+            return Err(ConversionError::InvalidOptions(format!(
+                "output template '{}' gives several tracks the same name '{}'; add {{index}} or {{track}}",
+                template, output_path
+            )));
+        }
+        tracks.push(TrackOutput { stream, track_number: position + 1, output_path });
+    }
+
+    Ok(tracks)
+}
+
+// Keeps titles usable as file names on every platform
+pub fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>()
+        .trim()
+        .trim_matches('.')
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn media() -> MediaInfo {
+        MediaInfo::from_json(
+            r#"{
+            "streams": [
+                {"index": 0, "codec_type": "video"},
+                {"index": 1, "codec_type": "audio", "tags": {"language": "eng", "title": "Main"}},
+                {"index": 2, "codec_type": "audio", "tags": {"language": "eng", "title": "Director: Commentary"}},
+                {"index": 3, "codec_type": "audio"}
+            ],
+            "format": {"format_name": "matroska,webm", "duration": "60.0"}
+        }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_plan_all_tracks() {
+        let tracks = plan_tracks(&media(), "/videos/film.mkv", "out/{stem}_{track}_{lang}.mp3", &[]).unwrap();
+        let paths: Vec<&str> = tracks.iter().map(|t| t.output_path.as_str()).collect();
+        assert_eq!(paths, ["out/film_1_eng.mp3", "out/film_2_eng.mp3", "out/film_3_und.mp3"]);
+        assert_eq!(tracks[2].stream.index, 3);
+    }
+
+    #[test]
+    fn test_plan_subset_with_titles() {
+        let selectors = ["a:1".parse().unwrap(), "3".parse().unwrap(), "a:1".parse().unwrap()];
+        let tracks = plan_tracks(&media(), "film.mkv", "{index} - {title}.flac", &selectors).unwrap();
+        let paths: Vec<&str> = tracks.iter().map(|t| t.output_path.as_str()).collect();
+        assert_eq!(paths, ["2 - Director_ Commentary.flac", "3 - track3.flac"]);
+    }
+
+    #[test]
+    fn test_plan_rejects_colliding_names() {
+        let result = plan_tracks(&media(), "film.mkv", "{lang}.mp3", &[]);
+        assert!(matches!(result, Err(ConversionError::InvalidOptions(_))));
+    }
+}
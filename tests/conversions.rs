@@ -0,0 +1,276 @@
+// End-to-end conversions of inputs generated with ffmpeg's lavfi sources.
+// They need ffmpeg and ffprobe on the PATH, so they only run when asked for:
+// `cargo test -- --ignored`.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+use video_audio_converter::{
+    ConversionError, ConversionOptions, MediaInfo, OutputFormat, SilenceOptions, StreamSelector, VideoToAudioConverter,
+};
+
+// Encoder delay and frame padding make lossy outputs run a little long
+const DURATION_TOLERANCE: f64 = 0.1;
+
+// A temp dir of generated inputs, removed when dropped
+struct Fixtures {
+    dir: PathBuf,
+    converter: VideoToAudioConverter,
+}
+
+impl Fixtures {
+    fn new(name: &str) -> Self {
+        let converter = VideoToAudioConverter::new().expect("these tests need ffmpeg and ffprobe");
+        let dir = std::env::temp_dir().join(format!("vac_fixtures_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Self { dir, converter }
+    }
+
+    fn path(&self, name: &str) -> String {
+        self.dir.join(name).to_string_lossy().into_owned()
+    }
+
+    // Runs ffmpeg with `args` and writes `name` in the fixture dir
+    fn generate(&self, name: &str, args: &[&str]) -> String {
+        let path = self.path(name);
+        let output = Command::new(self.converter.ffmpeg_path().unwrap())
+            .args(["-hide_banner", "-loglevel", "error", "-y"])
+            .args(args)
+            .arg(&path)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "generating {} failed: {}", name, String::from_utf8_lossy(&output.stderr));
+        path
+    }
+
+    // Small test-pattern video of `seconds` with the given audio sources mapped
+    // in order; `extra` holds output options such as metadata
+    fn video(&self, name: &str, seconds: f64, audio: &[&str], extra: &[&str]) -> String {
+        let picture = format!("testsrc=size=64x48:rate=10:duration={}", seconds);
+        let mut args = vec!["-f".to_string(), "lavfi".to_string(), "-i".to_string(), picture];
+        for source in audio {
+            args.extend(["-f", "lavfi", "-t", &seconds.to_string(), "-i", *source].map(String::from));
+        }
+        args.extend(["-map", "0:v"].map(String::from));
+        for i in 1..=audio.len() {
+            args.extend(["-map".to_string(), format!("{}:a", i)]);
+        }
+        args.extend(extra.iter().map(|arg| arg.to_string()));
+        args.extend(["-c:v", "mpeg4", "-c:a", "pcm_s16le"].map(String::from));
+        self.generate(name, &args.iter().map(String::as_str).collect::<Vec<_>>())
+    }
+
+    // Stereo 440 Hz tone
+    fn sine(&self, name: &str, seconds: f64) -> String {
+        self.video(name, seconds, &["sine=frequency=440:sample_rate=48000,pan=stereo|c0=c0|c1=c0"], &[])
+    }
+
+    fn probe(&self, path: &str) -> MediaInfo {
+        self.converter.probe(path).unwrap()
+    }
+}
+
+impl Drop for Fixtures {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn assert_duration(info: &MediaInfo, expected: f64) {
+    let duration = info.duration_seconds.expect("output has a duration");
+    assert!((duration - expected).abs() <= DURATION_TOLERANCE, "duration {} is not within {} of {}", duration, DURATION_TOLERANCE, expected);
+}
+
+// Tag names differ in case between containers (ID3 "title", Vorbis "TITLE")
+fn tag<'a>(info: &'a MediaInfo, key: &str) -> Option<&'a str> {
+    info.tags.iter().find(|(k, _)| k.eq_ignore_ascii_case(key)).map(|(_, v)| v.as_str())
+}
+
+fn codec_name(format: OutputFormat) -> &'static str {
+    match format {
+        OutputFormat::Mp3 => "mp3",
+        OutputFormat::Aac => "aac",
+        OutputFormat::Opus => "opus",
+        OutputFormat::Vorbis => "vorbis",
+        OutputFormat::Flac => "flac",
+        OutputFormat::Wav => "pcm_s16le",
+        OutputFormat::Alac => "alac",
+        OutputFormat::Ac3 => "ac3",
+    }
+}
+
+#[test]
+#[ignore = "needs ffmpeg; run with `cargo test -- --ignored`"]
+fn test_convert_to_every_supported_format() {
+    let fixtures = Fixtures::new("formats");
+    let input = fixtures.sine("tone.mkv", 3.0);
+    // Native encoders every build has, so the loop always checks something
+    assert!(fixtures.converter.capabilities().supports(OutputFormat::Flac));
+    assert!(fixtures.converter.capabilities().supports(OutputFormat::Wav));
+
+    for format in OutputFormat::ALL {
+        if !fixtures.converter.capabilities().supports(format) {
+            continue;
+        }
+        let output = fixtures.path(&format!("tone.{}", format.extensions()[0]));
+        fixtures.converter.convert(&input, &output, &ConversionOptions::new(), |_| {}).unwrap();
+
+        let info = fixtures.probe(&output);
+        assert_duration(&info, 3.0);
+        let streams: Vec<_> = info.streams.iter().collect();
+        assert_eq!(streams.len(), 1, "{} output should hold only audio", format);
+        assert_eq!(streams[0].codec.as_deref(), Some(codec_name(format)), "{}", format);
+        assert_eq!(streams[0].channels, Some(2), "{}", format);
+    }
+}
+
+#[test]
+#[ignore = "needs ffmpeg; run with `cargo test -- --ignored`"]
+fn test_odd_duration_with_audio_options() {
+    let fixtures = Fixtures::new("odd_duration");
+    let input = fixtures.sine("short.mkv", 1.37);
+    assert_duration(&fixtures.probe(&input), 1.37);
+
+    let output = fixtures.path("short.flac");
+    let options = ConversionOptions::new().channels(1).sample_rate(22050);
+    fixtures.converter.convert(&input, &output, &options, |_| {}).unwrap();
+    let info = fixtures.probe(&output);
+    assert_duration(&info, 1.37);
+    assert_eq!(info.streams[0].channels, Some(1));
+    assert_eq!(info.streams[0].sample_rate, Some(22050));
+
+    let output = fixtures.path("segment.wav");
+    let options = ConversionOptions::new().start(0.25).duration(0.5);
+    fixtures.converter.convert(&input, &output, &options, |_| {}).unwrap();
+    assert_duration(&fixtures.probe(&output), 0.5);
+}
+
+#[test]
+#[ignore = "needs ffmpeg; run with `cargo test -- --ignored`"]
+fn test_tags_are_carried_over() {
+    let fixtures = Fixtures::new("tags");
+    let input = fixtures.video(
+        "tagged.mkv",
+        2.0,
+        &["sine=frequency=440"],
+        &["-metadata", "title=Fixture Tone", "-metadata", "artist=Test Signal"],
+    );
+
+    for name in ["tagged.flac", "tagged.wav"] {
+        let output = fixtures.path(name);
+        fixtures.converter.convert(&input, &output, &ConversionOptions::new(), |_| {}).unwrap();
+        let info = fixtures.probe(&output);
+        assert_eq!(tag(&info, "title"), Some("Fixture Tone"), "{}", name);
+        assert_eq!(tag(&info, "artist"), Some("Test Signal"), "{}", name);
+    }
+}
+
+#[test]
+#[ignore = "needs ffmpeg; run with `cargo test -- --ignored`"]
+fn test_multi_track_input() {
+    let fixtures = Fixtures::new("tracks");
+    let input = fixtures.video(
+        "dubbed.mkv",
+        2.0,
+        &["sine=frequency=440", "sine=frequency=880,pan=stereo|c0=c0|c1=c0"],
+        &["-metadata:s:a:0", "language=eng", "-metadata:s:a:1", "language=ger"],
+    );
+    let languages: Vec<_> = fixtures.probe(&input).audio_streams().map(|s| s.language.clone()).collect();
+    assert_eq!(languages, [Some("eng".to_string()), Some("ger".to_string())]);
+
+    let template = fixtures.path("{stem}_{lang}.flac");
+    let tracks = fixtures.converter.extract_tracks(&input, &template, &[], &ConversionOptions::new(), |_| {}).unwrap();
+    let channels: Vec<_> = tracks.iter().map(|track| fixtures.probe(&track.output_path).streams[0].channels).collect();
+    assert_eq!(channels, [Some(1), Some(2)]);
+    for track in &tracks {
+        assert_duration(&fixtures.probe(&track.output_path), 2.0);
+    }
+
+    let output = fixtures.path("german.wav");
+    let options = ConversionOptions::new().audio_stream("lang:de".parse::<StreamSelector>().unwrap());
+    fixtures.converter.convert(&input, &output, &options, |_| {}).unwrap();
+    assert_eq!(fixtures.probe(&output).streams[0].channels, Some(2));
+}
+
+#[test]
+#[ignore = "needs ffmpeg; run with `cargo test -- --ignored`"]
+fn test_split_chaptered_input() {
+    let fixtures = Fixtures::new("chapters");
+    let metadata = fixtures.path("chapters.txt");
+    std::fs::write(
+        &metadata,
+        ";FFMETADATA1\n\
+         [CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=1000\ntitle=Intro\n\
+         [CHAPTER]\nTIMEBASE=1/1000\nSTART=1000\nEND=2500\ntitle=Middle\n\
+         [CHAPTER]\nTIMEBASE=1/1000\nSTART=2500\nEND=4500\ntitle=Outro\n",
+    )
+    .unwrap();
+    let input = fixtures.generate(
+        "book.mkv",
+        &[
+            "-f", "lavfi", "-i", "sine=frequency=440:duration=4.5",
+            "-f", "ffmetadata", "-i", &metadata,
+            "-map", "0:a", "-map_chapters", "1", "-c:a", "pcm_s16le",
+        ],
+    );
+    assert_eq!(fixtures.probe(&input).chapters.len(), 3);
+
+    // FLAC has a native encoder and keeps both tags
+    let template = fixtures.path("{chapter} - {title}.flac");
+    let outputs = fixtures.converter.split_by_chapters(&input, &template, &ConversionOptions::new(), |_| {}).unwrap();
+    assert_eq!(outputs.len(), 3);
+
+    for (output, (title, length)) in outputs.iter().zip([("Intro", 1.0), ("Middle", 1.5), ("Outro", 2.0)]) {
+        let info = fixtures.probe(&output.output_path);
+        assert_duration(&info, length);
+        assert_eq!(info.chapters.len(), 0);
+        assert_eq!(tag(&info, "title"), Some(title));
+        assert_eq!(tag(&info, "track"), Some(format!("{}/3", output.number).as_str()));
+    }
+}
+
+#[test]
+#[ignore = "needs ffmpeg; run with `cargo test -- --ignored`"]
+fn test_silence_detection_and_splitting() {
+    let fixtures = Fixtures::new("silence");
+    // Tone, two seconds of silence, tone
+    let input = fixtures.video("gap.mkv", 6.0, &["aevalsrc=if(between(t\\,2\\,4)\\,0\\,sin(440*2*PI*t)):sample_rate=48000"], &[]);
+    let silences = fixtures.converter.detect_silence(&input, &ConversionOptions::new(), &SilenceOptions::new()).unwrap();
+    assert_eq!(silences.len(), 1, "{:?}", silences);
+    assert!((silences[0].start_seconds - 2.0).abs() < DURATION_TOLERANCE, "{:?}", silences);
+    assert!((silences[0].end_seconds - 4.0).abs() < DURATION_TOLERANCE, "{:?}", silences);
+
+    let template = fixtures.path("gap_{part}.wav");
+    let (_, outputs) = fixtures.converter.split_by_silence(&input, &template, &ConversionOptions::new(), &SilenceOptions::new(), |_| {}).unwrap();
+    assert_eq!(outputs.len(), 2);
+    // Each cut lands in the middle of the gap
+    for output in &outputs {
+        assert_duration(&fixtures.probe(&output.output_path), 3.0);
+    }
+
+    // All-silent input is a single silence covering the whole file
+    let input = fixtures.video("quiet.mkv", 2.0, &["anullsrc=channel_layout=stereo:sample_rate=48000"], &[]);
+    let silences = fixtures.converter.detect_silence(&input, &ConversionOptions::new(), &SilenceOptions::new()).unwrap();
+    assert_eq!(silences.len(), 1, "{:?}", silences);
+    assert!(silences[0].start_seconds < DURATION_TOLERANCE);
+
+    let output = fixtures.path("quiet.flac");
+    fixtures.converter.convert(&input, &output, &ConversionOptions::new(), |_| {}).unwrap();
+    assert_duration(&fixtures.probe(&output), 2.0);
+}
+
+#[test]
+#[ignore = "needs ffmpeg; run with `cargo test -- --ignored`"]
+fn test_input_without_audio() {
+    let fixtures = Fixtures::new("no_audio");
+    let input = fixtures.video("silent_film.mkv", 1.0, &[], &[]);
+    let info = fixtures.probe(&input);
+    assert!(!info.has_audio());
+    assert_eq!(info.streams.len(), 1);
+
+    let output = fixtures.path("silent_film.mp3");
+    let result = fixtures.converter.convert(&input, &output, &ConversionOptions::new(), |_| {});
+    assert!(matches!(result, Err(ConversionError::StreamNotFound(_))), "{:?}", result);
+    assert!(!std::path::Path::new(&output).exists());
+}
@@ -0,0 +1,94 @@
+use std::sync::mpsc;
+
+use video_audio_converter::backend::{ScriptedBackend, ScriptedRun};
+use video_audio_converter::batch::{BatchOptions, collect_jobs};
+use video_audio_converter::events::forward_to;
+use video_audio_converter::{
+    Capabilities, ConversionError, ConversionOptions, MediaInfo, OutputFormat, ProgressEvent, StreamSelector, VideoToAudioConverter,
+};
+
+const PROBE_JSON: &str = r#"{
+    "streams": [
+        {"index": 0, "codec_type": "video", "codec_name": "h264"},
+        {"index": 1, "codec_type": "audio", "codec_name": "aac", "channels": 2, "sample_rate": "48000",
+         "tags": {"language": "eng"}, "disposition": {"default": 1}},
+        {"index": 2, "codec_type": "audio", "codec_name": "ac3", "channels": 6, "sample_rate": "48000",
+         "tags": {"language": "ger"}}
+    ],
+    "format": {"format_name": "mov,mp4,m4a,3gp,3g2,mj2", "duration": "90.5"}
+}"#;
+
+#[test]
+fn test_options_and_formats() {
+    assert_eq!("m4a".parse::<OutputFormat>().unwrap(), OutputFormat::Aac);
+    assert_eq!(OutputFormat::from_path("song.flac").unwrap(), OutputFormat::Flac);
+    assert!(OutputFormat::Mp3.check_output_path("song.wav").is_err());
+
+    let options = ConversionOptions::new().format(OutputFormat::Opus).bitrate(96).channels(2);
+    assert_eq!(options.resolve_format("talk.ogg").unwrap(), OutputFormat::Opus);
+    let args = options.encoder_args(OutputFormat::Opus, "talk.ogg").unwrap();
+    assert!(args.windows(2).any(|pair| pair == ["-acodec", "libopus"]));
+}
+
+#[test]
+fn test_select_stream_from_probe_output() {
+    let info = MediaInfo::from_json(PROBE_JSON).unwrap();
+    assert_eq!(info.duration_seconds, Some(90.5));
+    assert_eq!(info.audio_streams().count(), 2);
+
+    let german: StreamSelector = "lang:de".parse().unwrap();
+    assert_eq!(german.select(&info).unwrap().index, 2);
+    assert_eq!(StreamSelector::Default.select(&info).unwrap().index, 1);
+    assert!(matches!(StreamSelector::AudioIndex(5).select(&info), Err(ConversionError::StreamNotFound(_))));
+}
+
+#[test]
+fn test_collect_batch_jobs() {
+    let dir = std::env::temp_dir().join(format!("vac_library_batch_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("season1")).unwrap();
+    std::fs::write(dir.join("season1/e01.mkv"), b"video").unwrap();
+    std::fs::write(dir.join("trailer.mp4"), b"video").unwrap();
+
+    let sources = [dir.to_string_lossy().into_owned()];
+    let jobs = collect_jobs(&sources, &dir.join("out"), "mp3", &BatchOptions::new().recursive(true)).unwrap();
+    let outputs: Vec<_> = jobs.iter().map(|job| job.output.strip_prefix(&dir).unwrap().to_path_buf()).collect();
+    assert_eq!(outputs, ["out/season1/e01.mp3", "out/trailer.mp3"].map(std::path::PathBuf::from));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_missing_input_reports_failure() {
+    let backend = ScriptedBackend::new();
+    let converter = VideoToAudioConverter::with_backend(backend.clone(), Capabilities::default());
+    let (sender, receiver) = mpsc::channel();
+    let result = converter.convert("nonexistent.mp4", "output.mp3", &ConversionOptions::new(), forward_to(sender));
+    assert!(matches!(result, Err(ConversionError::FileNotFound)));
+
+    let events: Vec<ProgressEvent> = receiver.iter().collect();
+    assert!(matches!(events.as_slice(), [ProgressEvent::Failed(ConversionError::FileNotFound)]));
+    assert!(backend.commands().is_empty());
+}
+
+#[test]
+fn test_extract_tracks_with_scripted_backend() {
+    let backend = ScriptedBackend::new()
+        .media("movie.mp4", MediaInfo::from_json(PROBE_JSON).unwrap())
+        .run(ScriptedRun::new("out_time_us=90500000\nprogress=end\n").output("flac"));
+    let capabilities = Capabilities::parse(" ---\n A....D flac  FLAC\n", " ---\n  E  flac  FLAC\n");
+    let converter = VideoToAudioConverter::with_backend(backend.clone(), capabilities);
+    assert_eq!(converter.ffmpeg_path(), None);
+
+    let dir = std::env::temp_dir().join(format!("vac_library_tracks_{}", std::process::id()));
+    let template = dir.join("{stem}_{lang}.flac").to_string_lossy().into_owned();
+    let outputs = converter.extract_tracks("movie.mp4", &template, &[], &ConversionOptions::new(), |_| {}).unwrap();
+    let names: Vec<_> = outputs.iter().map(|track| std::path::Path::new(&track.output_path).file_name().unwrap()).collect();
+    assert_eq!(names, ["movie_eng.flac", "movie_ger.flac"]);
+
+    // One ffmpeg run maps both audio streams
+    let args = &backend.commands()[0];
+    assert!(args.windows(2).any(|pair| pair == ["-map", "0:1"]));
+    assert!(args.windows(2).any(|pair| pair == ["-map", "0:2"]));
+    let _ = std::fs::remove_dir_all(dir);
+}